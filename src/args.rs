@@ -1,13 +1,109 @@
+use std::path::PathBuf;
+
 use anyhow::Context;
 
+/// The file operand that stands for standard input.
+pub const STDIN: &str = "-";
+
+/// The name standard input is displayed with wherever a file name is printed.
+pub const STDIN_LABEL: &str = "<stdin>";
+
+/// What minigrep does with the matches it finds. Every mode carries the
+/// `Searchmode` its patterns are matched with.
 #[derive(Debug, Clone, Copy)]
 pub enum Mode {
+    /// Print the matching lines.
     Search(Searchmode),
+    /// Print the number of matching lines of each file. This correspondes to the '-c' flag
+    Count(Searchmode),
+    /// Print the number of matches in each file. This correspondes to the '--count-matches'
+    /// flag
+    CountMatches(Searchmode),
+    /// Print the paths of files with a match. This correspondes to the '-l' flag
+    FilesWithMatches(Searchmode),
+    /// Print the paths of files without a match. This correspondes to the
+    /// '--files-without-match' flag
+    FilesWithoutMatch(Searchmode),
+    /// Rewrite the matching files with every match replaced. This correspondes
+    /// to the '--rewrite' flag
+    Rewrite(Searchmode),
+}
+
+impl Mode {
+    /// Returns how patterns are matched in this mode.
+    pub fn searchmode(&self) -> Searchmode {
+        match *self {
+            Mode::Search(mode)
+            | Mode::Count(mode)
+            | Mode::CountMatches(mode)
+            | Mode::FilesWithMatches(mode)
+            | Mode::FilesWithoutMatch(mode)
+            | Mode::Rewrite(mode) => mode,
+        }
+    }
+
+    /// Returns the same mode, with patterns matched according to `searchmode`.
+    pub fn with_searchmode(self, searchmode: Searchmode) -> Mode {
+        match self {
+            Mode::Search(_) => Mode::Search(searchmode),
+            Mode::Count(_) => Mode::Count(searchmode),
+            Mode::CountMatches(_) => Mode::CountMatches(searchmode),
+            Mode::FilesWithMatches(_) => Mode::FilesWithMatches(searchmode),
+            Mode::FilesWithoutMatch(_) => Mode::FilesWithoutMatch(searchmode),
+            Mode::Rewrite(_) => Mode::Rewrite(searchmode),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum Searchmode {
+    /// Match patterns as fixed strings.
     Standard,
+    /// Match patterns as regular expressions. This correspondes to the '-E' flag
+    Regex,
+}
+
+/// The format results are printed in.
+#[derive(Debug, Clone, Copy)]
+pub enum OutputMode {
+    /// Human readable output, the file name as a heading above its matching
+    /// lines.
+    Standard,
+    /// One JSON object per line for every event of the search. This
+    /// correspondes to the '--json' flag
+    Json,
+    /// One line per match with its path, line number and column, as editors
+    /// expect it. This correspondes to the '--vimgrep' flag
+    Vimgrep,
+}
+
+/// How the case of letters is treated when matching.
+#[derive(Debug, Clone, Copy)]
+pub enum CaseMode {
+    /// Letters only match letters of the same case. This is the default and
+    /// correspondes to the '-s' flag
+    Sensitive,
+    /// Letters match regardless of their case. This correspondes to the '-i' flag
+    Insensitive,
+    /// Insensitive when no pattern contains an uppercase letter, sensitive
+    /// otherwise. This correspondes to the '-S' flag
+    Smart,
+}
+
+/// How files containing binary data are searched.
+#[derive(Debug, Clone, Copy)]
+pub enum BinaryMode {
+    /// Binary files found while searching a directory are skipped, when
+    /// binary data shows up in the first buffer read from them. Otherwise,
+    /// and for binary files given explicitly, the file is searched, but only
+    /// a summary is printed instead of the matching lines with binary data.
+    Auto,
+    /// Every binary file is searched, but only a summary is printed instead
+    /// of the matching lines. This correspondes to the '--binary' flag
+    SearchAndSuppress,
+    /// Binary data is not detected and every file is searched as text. This
+    /// correspondes to the '-a' flag
+    AsText,
 }
 
 #[allow(dead_code)]
@@ -18,20 +114,46 @@ pub struct Args {
     pub mode: Mode,
     pub positional: Vec<String>,
     pub pattern: String,
-    pub file: File<String>,
+    pub files: Files<String>,
 
     // Everything Else
-    pub patterns: Patterns<String>,
+    pub patterns: Patterns<PatternSource>,
+    /// Whether patterns were given with flags, even if there turned out to be
+    /// none, e.g., from an empty pattern file.
+    pub patterns_given: bool,
+    /// Whether patterns are read from stdin, which leaves nothing there to
+    /// search.
+    pub patterns_from_stdin: bool,
+    pub threads: usize,
+    pub case: CaseMode,
+    pub binary: BinaryMode,
+    pub quiet: bool,
+    pub color: termcolor::ColorChoice,
+    pub colors: Vec<crate::color::UserColorSpec>,
+    pub context: usize,
+    pub before_context: Option<usize>,
+    pub after_context: Option<usize>,
+    pub invert: bool,
+    pub only_matching: bool,
+    pub byte_offset: bool,
+    pub word: bool,
+    pub line: bool,
+    pub output: OutputMode,
+    pub replace: Option<String>,
+    pub dry_run: bool,
+    pub backup: bool,
+    pub hidden: bool,
+    pub no_ignore: bool,
+    pub globs: Vec<Glob>,
+    pub types: Vec<crate::types::Selection>,
+    pub type_defs: Vec<String>,
 }
 
 impl Args {
     /// Returns true if some non-zero number of matches is believed to be
     /// possible.
     pub fn matches_possible(&self) -> bool {
-        if self.positional.is_empty()
-            && self.pattern.is_empty()
-            && self.patterns.patterns.is_empty()
-        {
+        if self.positional.is_empty() && self.pattern.is_empty() && !self.patterns_given {
             return false;
         }
 
@@ -39,35 +161,106 @@ impl Args {
     }
 
     // Returns possible Pattern that is to be searched
+    //
+    // This must be called before `get_paths`, since without a pattern flag
+    // the first positional argument is the pattern.
     pub fn get_patterns(&mut self) -> anyhow::Result<Vec<String>> {
-        if !self.patterns.is_empty() {
-            return Ok(self.patterns.patterns.clone());
+        // patterns given with flags are all there is, even when there are
+        // none, in which case nothing matches
+        if self.patterns_given {
+            let mut patterns = Vec::new();
+            for source in &self.patterns.patterns {
+                match source {
+                    PatternSource::Pattern(pattern) => patterns.push(pattern.clone()),
+                    PatternSource::File(path) => patterns.extend(read_pattern_file(path)?),
+                }
+            }
+            return Ok(patterns);
         }
 
         if !self.pattern.is_empty() {
             return Ok(vec![self.pattern.clone()]);
         }
 
-        let pattern = vec![
-            self.positional
-                .pop()
-                .context("pattern to search not found")?,
-        ];
+        anyhow::ensure!(!self.positional.is_empty(), "pattern to search not found");
+        let pattern = vec![self.positional.remove(0)];
 
         Ok(pattern)
     }
 
-    pub fn get_file(&mut self) -> anyhow::Result<String> {
-        if !self.file.name.is_empty() {
-            return Ok(self.file.name.clone());
+    // Returns the paths to search, either files or directories to be walked
+    // recursively. Every file flag and every remaining positional argument
+    // is a path, and glob patterns among them are expanded. The path `-`
+    // stands for standard input.
+    pub fn get_paths(&mut self) -> anyhow::Result<Vec<PathBuf>> {
+        let operands = self
+            .files
+            .names
+            .iter()
+            .chain(self.positional.iter())
+            .collect::<Vec<_>>();
+
+        // without any file, standard input is searched instead
+        if operands.is_empty() {
+            return Ok(vec![PathBuf::from(STDIN)]);
+        }
+
+        let mut paths = Vec::new();
+        for operand in operands {
+            paths.extend(expand_glob(operand)?);
+        }
+
+        Ok(paths)
+    }
+
+    /// Returns the color choice to write output with.
+    ///
+    /// When colors are chosen automatically they are only used if stdout is
+    /// a terminal, so that piped output stays free of escape sequences.
+    pub fn get_color_choice(&self) -> termcolor::ColorChoice {
+        use std::io::IsTerminal;
+
+        match self.color {
+            termcolor::ColorChoice::Auto if !std::io::stdout().is_terminal() => {
+                termcolor::ColorChoice::Never
+            }
+            choice => choice,
+        }
+    }
+
+    /// Returns the number of lines to show before each match. An explicit
+    /// -B/--before-context wins over -C/--context, whatever their order.
+    ///
+    /// Context is only shown in modes that print whole lines.
+    pub fn get_before_context(&self) -> usize {
+        match self.mode {
+            Mode::Search(_) if self.shows_context() => self.before_context.unwrap_or(self.context),
+            _ => 0,
+        }
+    }
+
+    /// Returns the number of lines to show after each match. An explicit
+    /// -A/--after-context wins over -C/--context, whatever their order.
+    ///
+    /// Context is only shown in modes that print whole lines.
+    pub fn get_after_context(&self) -> usize {
+        match self.mode {
+            Mode::Search(_) if self.shows_context() => self.after_context.unwrap_or(self.context),
+            _ => 0,
         }
+    }
 
-        let file = self
-            .positional
-            .pop()
-            .context("file to be searched for pattern not provided")?;
+    /// Returns true if the output format has room for context lines.
+    fn shows_context(&self) -> bool {
+        !self.only_matching && !matches!(self.output, OutputMode::Vimgrep)
+    }
 
-        Ok(file)
+    /// Returns the number of threads to search with.
+    pub fn get_threads(&self) -> usize {
+        match self.threads {
+            0 => crate::walk::default_threads(),
+            threads => threads,
+        }
     }
 }
 
@@ -79,11 +272,45 @@ impl Default for Args {
             positional: Vec::new(),
             pattern: String::new(),
             patterns: Patterns::new(),
-            file: File::new(String::new()),
+            patterns_given: false,
+            patterns_from_stdin: false,
+            files: Files::new(),
+            threads: 0,
+            case: CaseMode::Sensitive,
+            binary: BinaryMode::Auto,
+            quiet: false,
+            color: termcolor::ColorChoice::Auto,
+            colors: Vec::new(),
+            context: 0,
+            before_context: None,
+            after_context: None,
+            invert: false,
+            only_matching: false,
+            byte_offset: false,
+            word: false,
+            line: false,
+            output: OutputMode::Standard,
+            replace: None,
+            dry_run: false,
+            backup: false,
+            hidden: false,
+            no_ignore: false,
+            globs: Vec::new(),
+            types: Vec::new(),
+            type_defs: Vec::new(),
         }
     }
 }
 
+/// A glob that files must match to be searched, or with a leading `!`, must
+/// not match.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pub glob: String,
+    /// Whether the glob ignores case. This correspondes to the '--iglob' flag
+    pub case_insensitive: bool,
+}
+
 /// A "special" mode that supercedes everything else.
 #[derive(Debug)]
 pub enum SpecialMode {
@@ -92,11 +319,57 @@ pub enum SpecialMode {
     /// Shows a very verbose version of the "help" output. This correspondes to the '--help' flag
     HelpLong,
 
-    /// Show condensed version information. e.g., `minigrep x.y.z`.
+    /// Show condensed version information. e.g., `minigrep x.y.z`. This correspondes to the '-V'
+    /// flag
     VersionShort,
     /// Show verbose version information. Includes "short" information as well as features included
     /// in the build
     VersionLong,
+
+    /// Show the table of file types. This correspondes to the '--type-list' flag. Carries the
+    /// definitions added with '--type-add', which are listed as well
+    TypeList(Vec<String>),
+}
+
+/// Where the patterns given with flags come from.
+#[derive(Debug)]
+pub enum PatternSource {
+    /// A pattern given with '-p/--pattern'.
+    Pattern(String),
+    /// The path of a file to read patterns from, given with
+    /// '-F/--pattern-file'. The path `-` stands for standard input.
+    File(String),
+}
+
+/// Returns the patterns in the file at `path`, one per line.
+///
+/// Empty lines and lines starting with `#` are skipped, and a pattern that
+/// starts with `#` is written as `\#`.
+fn read_pattern_file(path: &str) -> anyhow::Result<Vec<String>> {
+    use std::io::Read;
+
+    let mut contents = String::new();
+    if path == STDIN {
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .context("failed to read patterns from stdin")?;
+    } else {
+        std::fs::File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .with_context(|| format!("failed to read patterns from '{path}'"))?;
+    }
+
+    let mut patterns = Vec::new();
+    for line in contents.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let pattern = line.strip_prefix('\\').filter(|rest| rest.starts_with('#'));
+        patterns.push(pattern.unwrap_or(line).to_string());
+    }
+
+    Ok(patterns)
 }
 
 #[derive(Debug)]
@@ -111,8 +384,8 @@ impl<T> Patterns<T> {
         }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.patterns.is_empty()
+    pub fn push(&mut self, pattern: T) {
+        self.patterns.push(pattern);
     }
 }
 
@@ -128,20 +401,39 @@ where
 }
 
 #[derive(Debug)]
-pub struct File<T> {
-    pub name: T,
+pub struct Files<T> {
+    pub names: Vec<T>,
 }
 
-impl<T> File<T> {
-    pub fn new(name: T) -> Self {
-        File { name }
+impl<T> Files<T> {
+    pub fn new() -> Files<T> {
+        Files { names: Vec::new() }
+    }
+
+    pub fn push(&mut self, name: T) {
+        self.names.push(name);
     }
 }
 
-impl<T: Clone> Clone for File<T> {
-    fn clone(&self) -> File<T> {
-        File {
-            name: self.name.clone(),
-        }
+/// Expands `operand` into the paths it names.
+///
+/// An operand that exists on disk is always taken literally, even when it
+/// looks like a glob. Otherwise, if it contains glob syntax (e.g.,
+/// `src/**/*.rs`) it is expanded here rather than relying on the shell, which
+/// doesn't happen when the argument is quoted or on Windows shells.
+fn expand_glob(operand: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let path = PathBuf::from(operand);
+
+    if operand == STDIN || path.exists() || !operand.contains(['*', '?', '[']) {
+        return Ok(vec![path]);
     }
+
+    let mut paths = Vec::new();
+    for entry in glob::glob(operand).with_context(|| format!("invalid glob '{operand}'"))? {
+        paths.push(entry.with_context(|| format!("failed to expand glob '{operand}'"))?);
+    }
+
+    anyhow::ensure!(!paths.is_empty(), "no files match glob '{operand}'");
+
+    Ok(paths)
 }
@@ -0,0 +1,166 @@
+use termcolor::{Color, ColorSpec};
+
+/// The colors used for each kind of output.
+#[derive(Debug, Clone)]
+pub struct ColorSpecs {
+    pub path: ColorSpec,
+    pub line: ColorSpec,
+    pub matched: ColorSpec,
+}
+
+impl Default for ColorSpecs {
+    fn default() -> Self {
+        let mut path = ColorSpec::new();
+        path.set_fg(Some(Color::Magenta));
+
+        let mut line = ColorSpec::new();
+        line.set_fg(Some(Color::Green));
+
+        let mut matched = ColorSpec::new();
+        matched.set_fg(Some(Color::Red)).set_bold(true);
+
+        ColorSpecs {
+            path,
+            line,
+            matched,
+        }
+    }
+}
+
+impl ColorSpecs {
+    /// Returns the default colors with the user given specs applied on top,
+    /// in order.
+    pub fn new(user_specs: &[UserColorSpec]) -> ColorSpecs {
+        let mut specs = ColorSpecs::default();
+
+        for user_spec in user_specs {
+            let spec = match user_spec.out_type {
+                OutType::Path => &mut specs.path,
+                OutType::Line => &mut specs.line,
+                OutType::Match => &mut specs.matched,
+            };
+            user_spec.apply(spec);
+        }
+
+        specs
+    }
+}
+
+/// The kind of output a color spec applies to.
+#[derive(Debug, Clone, Copy)]
+enum OutType {
+    Path,
+    Line,
+    Match,
+}
+
+/// What a color spec changes.
+#[derive(Debug, Clone, Copy)]
+enum SpecKind {
+    /// Clears every color and style.
+    None,
+    Fg(Color),
+    Bg(Color),
+    Style(Style),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Style {
+    Bold,
+    NoBold,
+    Intense,
+    NoIntense,
+    Underline,
+    NoUnderline,
+}
+
+/// A single color spec given by the user, e.g., `match:fg:red`.
+///
+/// The format is `{type}:{attribute}:{value}`, where type is one of `path`,
+/// `line` or `match`, attribute is one of `fg`, `bg` or `style`, and value is
+/// a color or a style. `{type}:none` clears every color and style of a type.
+#[derive(Debug, Clone, Copy)]
+pub struct UserColorSpec {
+    out_type: OutType,
+    kind: SpecKind,
+}
+
+impl UserColorSpec {
+    fn apply(&self, spec: &mut ColorSpec) {
+        match self.kind {
+            SpecKind::None => {
+                spec.clear();
+            }
+            SpecKind::Fg(color) => {
+                spec.set_fg(Some(color));
+            }
+            SpecKind::Bg(color) => {
+                spec.set_bg(Some(color));
+            }
+            SpecKind::Style(style) => {
+                match style {
+                    Style::Bold => spec.set_bold(true),
+                    Style::NoBold => spec.set_bold(false),
+                    Style::Intense => spec.set_intense(true),
+                    Style::NoIntense => spec.set_intense(false),
+                    Style::Underline => spec.set_underline(true),
+                    Style::NoUnderline => spec.set_underline(false),
+                };
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for UserColorSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let parts = value.split(':').collect::<Vec<_>>();
+
+        let out_type = match parts[0] {
+            "path" => OutType::Path,
+            "line" => OutType::Line,
+            "match" => OutType::Match,
+            other => anyhow::bail!(
+                "unrecognized output type '{other}' in color spec '{value}', \
+                 expected one of path, line or match"
+            ),
+        };
+
+        let kind = match parts[1..] {
+            ["none"] => SpecKind::None,
+            ["fg", color] => SpecKind::Fg(parse_color(color)?),
+            ["bg", color] => SpecKind::Bg(parse_color(color)?),
+            ["style", style] => SpecKind::Style(parse_style(style)?),
+            _ => anyhow::bail!(
+                "invalid color spec '{value}', expected {{type}}:{{attribute}}:{{value}} \
+                 or {{type}}:none"
+            ),
+        };
+
+        Ok(UserColorSpec { out_type, kind })
+    }
+}
+
+fn parse_color(color: &str) -> anyhow::Result<Color> {
+    color
+        .parse()
+        .map_err(|err| anyhow::anyhow!("invalid color '{color}': {err}"))
+}
+
+fn parse_style(style: &str) -> anyhow::Result<Style> {
+    let style = match style {
+        "bold" => Style::Bold,
+        "nobold" => Style::NoBold,
+        "intense" => Style::Intense,
+        "nointense" => Style::NoIntense,
+        "underline" => Style::Underline,
+        "nounderline" => Style::NoUnderline,
+        other => anyhow::bail!(
+            "unrecognized style '{other}', expected one of bold, nobold, intense, \
+             nointense, underline or nounderline"
+        ),
+    };
+
+    Ok(style)
+}
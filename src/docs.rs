@@ -11,7 +11,7 @@ use super::flags::Flag;
 
 fn generate_version() -> String {
     let semver = option_env!("CARGO_PKG_VERSION").unwrap_or("N/A");
-    return semver.to_string();
+    semver.to_string()
 }
 
 /// Generates a short version string of the form `minigrep x.y.z`.
@@ -31,14 +31,19 @@ minigrep !!version!!
 Ar_Monarch <praffulthapa11>
 
 minigrep searches for PATTERNS in given FILE. minigrep prints each line that matches a patten.
+When FILE is a directory, every file below it is searched recursively, and FILE may be a
+glob such as 'src/**/*.rs'. Without FILE, or when FILE is '-', standard input is searched.
 
 Project Home Page: https://github.com/ArMonarch/minigrep
 
 USAGE: 
-    minigrep [OPTIONS] PATTERN FILE
-    minigrep [OPTIONS] PATTERN -f|--file FILE
-    minigrep [OPTIONS] -p|--pattern PATTERN FILE
-    minigrep [OPTIONS] -p|--pattern PATTERN -f|--file FILE
+    minigrep [OPTIONS] PATTERN [FILE...]
+    minigrep [OPTIONS] PATTERN -f|--file FILE...
+    minigrep [OPTIONS] -p|--pattern PATTERN [FILE...]
+    minigrep [OPTIONS] -p|--pattern PATTERN -f|--file FILE...
+    minigrep [OPTIONS] -E|--regex REGEX [FILE...]
+    minigrep [OPTIONS] -F|--pattern-file PATTERNFILE [FILE...]
+    minigrep [OPTIONS] --rewrite -r|--replace TEXT PATTERN FILE...
 
 OPTIONS:
 !!options!!
@@ -71,6 +76,9 @@ fn generate_flag_short(flag: &dyn Flag) -> (String, String) {
         let name = char::from(byte);
         write!(col_1, r"-{name}");
         write!(col_1, r", ");
+    } else {
+        // line up long names with those of flags that have a short name
+        write!(col_1, "    ");
     }
 
     write!(col_1, r"--{name}", name = flag.name_long());
@@ -79,10 +87,14 @@ fn generate_flag_short(flag: &dyn Flag) -> (String, String) {
 
     write!(col_2, "{}", flag.doc_short());
 
+    if flag.is_multivalued() {
+        write!(col_2, " (can be repeated)");
+    }
+
     (col_1, col_2)
 }
 
-fn format_short_colums<'a>(
+fn format_short_colums(
     column_1: Vec<String>,
     colums_2: Vec<String>,
     max_col_1: usize,
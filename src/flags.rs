@@ -1,7 +1,10 @@
 use std::ffi::OsString;
 use std::fmt::Debug;
 
+use anyhow::Context as _;
+
 use crate::args;
+use crate::types;
 
 /// Represents flag name, either &str OR u8
 #[derive(Debug)]
@@ -89,7 +92,7 @@ impl<I, O> FlagValue<I, O> {
     /// namely, callers usually know whether a switch or a value is expected.
     /// If a flag is something different, then it indicates a bug, and thus a
     /// panic is acceptable.
-    fn _unwrap_switch(self) -> I {
+    fn unwrap_switch(self) -> I {
         match self {
             FlagValue::Switch(val) => val,
             FlagValue::Value(_) => unreachable!("got flag value but expected switch"),
@@ -104,7 +107,7 @@ impl<I, O> FlagValue<I, O> {
     /// namely, callers usually know whether a switch or a value is expected.
     /// If a flag is something different, then it indicates a bug, and thus a
     /// panic is acceptable.
-    fn unwrap_value(self) -> O {
+    pub fn unwrap_value(self) -> O {
         match self {
             FlagValue::Value(val) => val,
             FlagValue::Switch(_) => unreachable!("got switch but expected flag value"),
@@ -169,6 +172,17 @@ pub enum FlagLookUp<'a> {
 pub trait Flag: Debug + Send + Sync + 'static {
     fn is_switch(&self) -> bool;
 
+    /// Whether the flag may be given more than once, with every value kept.
+    ///
+    /// The parser hands each value of a multivalued flag to `append`, which
+    /// adds it to those given before, e.g., `-p foo -p bar` searches for both
+    /// patterns. Every other flag is handed its value with `update`, which
+    /// overwrites the previous one, so the last occurrence wins. Multivalued
+    /// flags can't be switches.
+    fn is_multivalued(&self) -> bool {
+        false
+    }
+
     /// Flag short name is Optional
     fn name_short(&self) -> Option<u8> {
         None
@@ -180,11 +194,69 @@ pub trait Flag: Debug + Send + Sync + 'static {
 
     fn _doc_long(&self) -> &'static str;
 
-    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()>;
+    /// Sets the value of a flag that is not multivalued.
+    fn update(
+        &self,
+        _value: FlagValue<bool, String>,
+        _args: &mut args::Args,
+    ) -> anyhow::Result<()> {
+        unreachable!(
+            "--{} is multivalued, its values are appended",
+            self.name_long()
+        )
+    }
+
+    /// Appends a value of a multivalued flag.
+    fn append(&self, _value: String, _args: &mut args::Args) -> anyhow::Result<()> {
+        unreachable!(
+            "--{} is not multivalued, its value is updated",
+            self.name_long()
+        )
+    }
 }
 
 /// A list of all flags in minigrep via implementations of `Flag`.
-pub(super) const FLAGS: &[&dyn Flag] = &[&Patterns, &File];
+pub(super) const FLAGS: &[&dyn Flag] = &[
+    &Patterns,
+    &PatternFile,
+    &File,
+    &Regex,
+    &IgnoreCase,
+    &CaseSensitive,
+    &SmartCase,
+    &WordRegexp,
+    &LineRegexp,
+    &Text,
+    &Binary,
+    &Quiet,
+    &InvertMatch,
+    &Count,
+    &CountMatches,
+    &FilesWithMatches,
+    &FilesWithoutMatch,
+    &OnlyMatching,
+    &ByteOffset,
+    &Replace,
+    &Rewrite,
+    &DryRun,
+    &Backup,
+    &AfterContext,
+    &BeforeContext,
+    &Context,
+    &Glob,
+    &IGlob,
+    &Type,
+    &TypeNot,
+    &TypeAdd,
+    &TypeList,
+    &Hidden,
+    &NoIgnore,
+    &Json,
+    &Vimgrep,
+    &Color,
+    &Colors,
+    &Threads,
+];
 
 /// -p/--pattern
 #[derive(Debug)]
@@ -203,6 +275,10 @@ impl Flag for Patterns {
         "pattern"
     }
 
+    fn is_multivalued(&self) -> bool {
+        true
+    }
+
     fn doc_short(&self) -> &'static str {
         r"Search for given patterns"
     }
@@ -211,10 +287,56 @@ impl Flag for Patterns {
         ""
     }
 
-    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
-        let patterns = value.unwrap_value();
+    fn append(&self, value: String, args: &mut args::Args) -> anyhow::Result<()> {
+        args.patterns.push(args::PatternSource::Pattern(value));
+        args.patterns_given = true;
+        Ok(())
+    }
+}
+
+/// -F/--pattern-file
+#[derive(Debug)]
+struct PatternFile;
+
+impl Flag for PatternFile {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'F')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "pattern-file"
+    }
+
+    fn is_multivalued(&self) -> bool {
+        true
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Read patterns from the given file, '-' for stdin"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+Read patterns from the given file, one pattern per line. Empty lines and lines
+starting with '#' are ignored, a pattern that starts with '#' can be written
+as '\#'. When the file is '-', patterns are read from stdin, which then can't
+be searched as well.
+"
+    }
+
+    fn append(&self, value: String, args: &mut args::Args) -> anyhow::Result<()> {
+        // the file is only read once all flags are parsed, so that e.g.
+        // `-F - -h` shows the help instead of waiting for stdin
+        if value == args::STDIN {
+            args.patterns_from_stdin = true;
+        }
 
-        args.patterns = args::Patterns::from(patterns);
+        args.patterns.push(args::PatternSource::File(value));
+        args.patterns_given = true;
         Ok(())
     }
 }
@@ -236,8 +358,43 @@ impl Flag for File {
         "file"
     }
 
+    fn is_multivalued(&self) -> bool {
+        true
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Search the given file for patterns"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn append(&self, value: String, args: &mut args::Args) -> anyhow::Result<()> {
+        args.files.push(value);
+        Ok(())
+    }
+}
+
+/// -E/--regex
+#[derive(Debug)]
+struct Regex;
+
+impl Flag for Regex {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'E')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "regex"
+    }
+
     fn doc_short(&self) -> &'static str {
-        r"Search for the given file for patterns"
+        r"Treat patterns as regular expressions"
     }
 
     fn _doc_long(&self) -> &'static str {
@@ -245,21 +402,1227 @@ impl Flag for File {
     }
 
     fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
-        let file_name = value.unwrap_value();
+        let mode = match value.unwrap_switch() {
+            true => args::Searchmode::Regex,
+            false => args::Searchmode::Standard,
+        };
 
-        args.file = args::File::new(file_name);
+        args.mode = args.mode.with_searchmode(mode);
         Ok(())
     }
 }
 
-use crate::parse::{ParseResult, Parser};
-pub fn parse() -> ParseResult<args::Args> {
-    let parser = Parser::new();
-    let mut args = args::Args::default();
+/// -i/--ignore-case
+#[derive(Debug)]
+struct IgnoreCase;
 
-    if let Err(err) = parser.parse(std::env::args().skip(1), &mut args) {
-        return ParseResult::Err(err);
-    };
+impl Flag for IgnoreCase {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'i')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "ignore-case"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Search case insensitively"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let case = match value.unwrap_switch() {
+            true => args::CaseMode::Insensitive,
+            false => args::CaseMode::Sensitive,
+        };
+
+        args.case = case;
+        Ok(())
+    }
+}
+
+/// -s/--case-sensitive
+#[derive(Debug)]
+struct CaseSensitive;
+
+impl Flag for CaseSensitive {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b's')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "case-sensitive"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Search case sensitively, this is the default"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        if value.unwrap_switch() {
+            args.case = args::CaseMode::Sensitive;
+        }
+        Ok(())
+    }
+}
+
+/// -S/--smart-case
+#[derive(Debug)]
+struct SmartCase;
+
+impl Flag for SmartCase {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'S')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "smart-case"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Search case insensitively if all patterns are lowercase"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let case = match value.unwrap_switch() {
+            true => args::CaseMode::Smart,
+            false => args::CaseMode::Sensitive,
+        };
+
+        args.case = case;
+        Ok(())
+    }
+}
+
+/// -w/--word-regexp
+#[derive(Debug)]
+struct WordRegexp;
+
+impl Flag for WordRegexp {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'w')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "word-regexp"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Only match whole words"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.word = value.unwrap_switch();
+        Ok(())
+    }
+}
+
+/// -x/--line-regexp
+#[derive(Debug)]
+struct LineRegexp;
+
+impl Flag for LineRegexp {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'x')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "line-regexp"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Only match whole lines"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.line = value.unwrap_switch();
+        Ok(())
+    }
+}
+
+/// -a/--text
+#[derive(Debug)]
+struct Text;
+
+impl Flag for Text {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'a')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "text"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Search binary files as if they were text"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let binary = match value.unwrap_switch() {
+            true => args::BinaryMode::AsText,
+            false => args::BinaryMode::Auto,
+        };
+
+        args.binary = binary;
+        Ok(())
+    }
+}
+
+/// --binary
+#[derive(Debug)]
+struct Binary;
+
+impl Flag for Binary {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_long(&self) -> &'static str {
+        "binary"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Search binary files found in directories instead of skipping them"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let binary = match value.unwrap_switch() {
+            true => args::BinaryMode::SearchAndSuppress,
+            false => args::BinaryMode::Auto,
+        };
+
+        args.binary = binary;
+        Ok(())
+    }
+}
+
+/// -q/--quiet
+#[derive(Debug)]
+struct Quiet;
+
+impl Flag for Quiet {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'q')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "quiet"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Print nothing, exit with status 0 as soon as a match is found"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.quiet = value.unwrap_switch();
+        Ok(())
+    }
+}
+
+/// -v/--invert-match
+#[derive(Debug)]
+struct InvertMatch;
+
+impl Flag for InvertMatch {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'v')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "invert-match"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Print lines that don't match any pattern"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.invert = value.unwrap_switch();
+        Ok(())
+    }
+}
+
+/// -c/--count
+#[derive(Debug)]
+struct Count;
+
+impl Flag for Count {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'c')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "count"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Print only the number of matching lines of each file"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let searchmode = args.mode.searchmode();
+
+        args.mode = match value.unwrap_switch() {
+            true => args::Mode::Count(searchmode),
+            false => args::Mode::Search(searchmode),
+        };
+        Ok(())
+    }
+}
+
+/// --count-matches
+#[derive(Debug)]
+struct CountMatches;
+
+impl Flag for CountMatches {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_long(&self) -> &'static str {
+        "count-matches"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Print only the number of matches in each file"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let searchmode = args.mode.searchmode();
+
+        args.mode = match value.unwrap_switch() {
+            true => args::Mode::CountMatches(searchmode),
+            false => args::Mode::Search(searchmode),
+        };
+        Ok(())
+    }
+}
+
+/// -l/--files-with-matches
+#[derive(Debug)]
+struct FilesWithMatches;
+
+impl Flag for FilesWithMatches {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'l')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "files-with-matches"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Print only the paths of files with at least one match"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let searchmode = args.mode.searchmode();
+
+        args.mode = match value.unwrap_switch() {
+            true => args::Mode::FilesWithMatches(searchmode),
+            false => args::Mode::Search(searchmode),
+        };
+        Ok(())
+    }
+}
+
+/// --files-without-match
+#[derive(Debug)]
+struct FilesWithoutMatch;
+
+impl Flag for FilesWithoutMatch {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_long(&self) -> &'static str {
+        "files-without-match"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Print only the paths of files without any match"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let searchmode = args.mode.searchmode();
+
+        args.mode = match value.unwrap_switch() {
+            true => args::Mode::FilesWithoutMatch(searchmode),
+            false => args::Mode::Search(searchmode),
+        };
+        Ok(())
+    }
+}
+
+/// -o/--only-matching
+#[derive(Debug)]
+struct OnlyMatching;
+
+impl Flag for OnlyMatching {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'o')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "only-matching"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Print only the matched parts of lines, one per line"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.only_matching = value.unwrap_switch();
+        Ok(())
+    }
+}
+
+/// -b/--byte-offset
+#[derive(Debug)]
+struct ByteOffset;
+
+impl Flag for ByteOffset {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'b')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "byte-offset"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Print the byte offset of each line, or of each match with -o"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.byte_offset = value.unwrap_switch();
+        Ok(())
+    }
+}
+
+/// -r/--replace
+#[derive(Debug)]
+struct Replace;
+
+impl Flag for Replace {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'r')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "replace"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Print matches replaced by the given text"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+Print every match replaced by the given text. Files are never changed. With
+-E/--regex, '$1' or '${name}' in the text is replaced by what the capture
+group with that index or name matched, and '$$' stands for a literal '$'.
+"
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.replace = Some(value.unwrap_value());
+        Ok(())
+    }
+}
+
+/// --rewrite
+#[derive(Debug)]
+struct Rewrite;
+
+impl Flag for Rewrite {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_long(&self) -> &'static str {
+        "rewrite"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Rewrite matching files with the -r/--replace text"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+Rewrite every matching file in place, with each match replaced by the text
+given with -r/--replace, and print the path of each file rewritten. A file is
+first written to a temporary file next to it, which then replaces it, so that
+it is never left half written. Permissions are kept. Binary files are never
+rewritten.
+"
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let searchmode = args.mode.searchmode();
+
+        args.mode = match value.unwrap_switch() {
+            true => args::Mode::Rewrite(searchmode),
+            false => args::Mode::Search(searchmode),
+        };
+        Ok(())
+    }
+}
+
+/// --dry-run
+#[derive(Debug)]
+struct DryRun;
+
+impl Flag for DryRun {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_long(&self) -> &'static str {
+        "dry-run"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"With --rewrite, print a diff instead of changing files"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.dry_run = value.unwrap_switch();
+        Ok(())
+    }
+}
+
+/// --backup
+#[derive(Debug)]
+struct Backup;
+
+impl Flag for Backup {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_long(&self) -> &'static str {
+        "backup"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"With --rewrite, keep the original of each file as FILE.bak"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.backup = value.unwrap_switch();
+        Ok(())
+    }
+}
+
+/// -A/--after-context
+#[derive(Debug)]
+struct AfterContext;
+
+impl Flag for AfterContext {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'A')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "after-context"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Show NUM lines after each match"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let lines = value.unwrap_value();
+
+        args.after_context = Some(
+            lines
+                .parse()
+                .with_context(|| format!("invalid number of context lines '{lines}'"))?,
+        );
+        Ok(())
+    }
+}
+
+/// -B/--before-context
+#[derive(Debug)]
+struct BeforeContext;
+
+impl Flag for BeforeContext {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'B')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "before-context"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Show NUM lines before each match"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let lines = value.unwrap_value();
+
+        args.before_context = Some(
+            lines
+                .parse()
+                .with_context(|| format!("invalid number of context lines '{lines}'"))?,
+        );
+        Ok(())
+    }
+}
+
+/// -C/--context
+#[derive(Debug)]
+struct Context;
+
+impl Flag for Context {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'C')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "context"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Show NUM lines before and after each match"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let lines = value.unwrap_value();
+
+        args.context = lines
+            .parse()
+            .with_context(|| format!("invalid number of context lines '{lines}'"))?;
+        Ok(())
+    }
+}
+
+/// -g/--glob
+#[derive(Debug)]
+struct Glob;
+
+impl Flag for Glob {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'g')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "glob"
+    }
+
+    fn is_multivalued(&self) -> bool {
+        true
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Only search files matching the glob, or not with a leading '!'"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+Only search the files matching the glob, e.g., '*.rs', or with a leading '!',
+those that don't match it, e.g., '!*.lock'. Globs use the syntax of
+'.gitignore' files, and those with a '/' in them are relative to the current
+directory. When a file matches several globs, the last one given wins. Globs
+win over ignore files and apply to the files given on the command line too.
+"
+    }
+
+    fn append(&self, value: String, args: &mut args::Args) -> anyhow::Result<()> {
+        args.globs.push(args::Glob {
+            glob: value,
+            case_insensitive: false,
+        });
+        Ok(())
+    }
+}
+
+/// --iglob
+#[derive(Debug)]
+struct IGlob;
+
+impl Flag for IGlob {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_long(&self) -> &'static str {
+        "iglob"
+    }
+
+    fn is_multivalued(&self) -> bool {
+        true
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Like -g/--glob, but ignoring case"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn append(&self, value: String, args: &mut args::Args) -> anyhow::Result<()> {
+        args.globs.push(args::Glob {
+            glob: value,
+            case_insensitive: true,
+        });
+        Ok(())
+    }
+}
+
+/// -t/--type
+#[derive(Debug)]
+struct Type;
+
+impl Flag for Type {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b't')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "type"
+    }
+
+    fn is_multivalued(&self) -> bool {
+        true
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Only search files of the given type, e.g., rust"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+Only search files of the given type. Types are named sets of globs, e.g.,
+'rust' for '*.rs', see --type-list for all of them. When given several times,
+files of any of the types are searched. Types only narrow down the files
+found in directories that are not hidden or ignored, and globs given with
+-g/--glob win over them.
+"
+    }
+
+    fn append(&self, value: String, args: &mut args::Args) -> anyhow::Result<()> {
+        args.types.push(types::Selection::Select(value));
+        Ok(())
+    }
+}
+
+/// -T/--type-not
+#[derive(Debug)]
+struct TypeNot;
+
+impl Flag for TypeNot {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'T')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "type-not"
+    }
+
+    fn is_multivalued(&self) -> bool {
+        true
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Don't search files of the given type"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn append(&self, value: String, args: &mut args::Args) -> anyhow::Result<()> {
+        args.types.push(types::Selection::Negate(value));
+        Ok(())
+    }
+}
+
+/// --type-add
+#[derive(Debug)]
+struct TypeAdd;
+
+impl Flag for TypeAdd {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_long(&self) -> &'static str {
+        "type-add"
+    }
+
+    fn is_multivalued(&self) -> bool {
+        true
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Add a file type as NAME:GLOB, e.g., 'proto:*.proto'"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+Add a glob to the file type with the given name, creating the type if it
+doesn't exist yet. The definition is of the form 'name:glob', e.g.,
+'proto:*.proto'. Added types can be used with -t/--type and -T/--type-not
+like built-in ones.
+"
+    }
+
+    fn append(&self, value: String, args: &mut args::Args) -> anyhow::Result<()> {
+        args.type_defs.push(value);
+        Ok(())
+    }
+}
+
+/// --type-list
+#[derive(Debug)]
+struct TypeList;
+
+impl Flag for TypeList {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_long(&self) -> &'static str {
+        "type-list"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Show all file types and their globs"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        if value.unwrap_switch() {
+            args.special = Some(args::SpecialMode::TypeList(Vec::new()));
+        }
+        Ok(())
+    }
+}
+
+/// --hidden
+#[derive(Debug)]
+struct Hidden;
+
+impl Flag for Hidden {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_long(&self) -> &'static str {
+        "hidden"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Search hidden files and directories"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+Search hidden files and directories, i.e., those whose name starts with a '.',
+when walking a directory. They are skipped by default. Files and directories
+given on the command line are always searched.
+"
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.hidden = value.unwrap_switch();
+        Ok(())
+    }
+}
+
+/// --no-ignore
+#[derive(Debug)]
+struct NoIgnore;
+
+impl Flag for NoIgnore {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_long(&self) -> &'static str {
+        "no-ignore"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Don't skip files matched by ignore files"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+Don't skip the files and directories matched by ignore files when walking a
+directory. By default, the rules of '.ignore', '.gitignore' and
+'.git/info/exclude' files are honoured, as well as git's global ignore file.
+"
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.no_ignore = value.unwrap_switch();
+        Ok(())
+    }
+}
+
+/// --json
+#[derive(Debug)]
+struct Json;
+
+impl Flag for Json {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_long(&self) -> &'static str {
+        "json"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Print results as JSON Lines"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+Print one JSON object per line for every event of the search: 'begin' and
+'end' around the results of each file with a match, 'match' and 'context' for
+lines, and a final 'summary' with statistics. Data that isn't valid UTF-8 is
+written as base64 under 'bytes' instead of 'text'.
+"
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.output = match value.unwrap_switch() {
+            true => args::OutputMode::Json,
+            false => args::OutputMode::Standard,
+        };
+        Ok(())
+    }
+}
+
+/// --vimgrep
+#[derive(Debug)]
+struct Vimgrep;
+
+impl Flag for Vimgrep {
+    fn is_switch(&self) -> bool {
+        true
+    }
+
+    fn name_long(&self) -> &'static str {
+        "vimgrep"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Print each match as path:line:column:text"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+Print every match on a line of its own, prefixed by the path, the line number
+and the 1-based byte column of the match, as editors like vim expect it. A
+line with several matches is printed once for each of them. Context isn't
+shown in this format.
+"
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        args.output = match value.unwrap_switch() {
+            true => args::OutputMode::Vimgrep,
+            false => args::OutputMode::Standard,
+        };
+        Ok(())
+    }
+}
+
+/// --color
+#[derive(Debug)]
+struct Color;
+
+impl Flag for Color {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_long(&self) -> &'static str {
+        "color"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"When to use colors: never, auto, always or ansi"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+When to use colors in the output. With 'auto', the default, colors are used
+only when stdout is a terminal. 'always' forces colors, and 'ansi' forces
+colors written as ANSI escape sequences even on Windows consoles.
+"
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let value = value.unwrap_value();
+
+        args.color = match value.as_str() {
+            "never" => termcolor::ColorChoice::Never,
+            "auto" => termcolor::ColorChoice::Auto,
+            "always" => termcolor::ColorChoice::Always,
+            "ansi" => termcolor::ColorChoice::AlwaysAnsi,
+            _ => anyhow::bail!(
+                "unrecognized color choice '{value}', expected one of never, auto, always or ansi"
+            ),
+        };
+        Ok(())
+    }
+}
+
+/// --colors
+#[derive(Debug)]
+struct Colors;
+
+impl Flag for Colors {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_long(&self) -> &'static str {
+        "colors"
+    }
+
+    fn is_multivalued(&self) -> bool {
+        true
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Color spec for path, line or match, e.g., 'match:fg:red'"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        r"
+Set the color of a kind of output, in the format {type}:{attribute}:{value}.
+Type is one of path, line or match. Attribute is fg, bg or style, where fg and
+bg take a color (e.g., red, blue or 0-255) and style takes one of bold, nobold,
+intense, nointense, underline or nounderline. '{type}:none' clears all colors
+and styles of a type. Later specs override earlier ones.
+"
+    }
+
+    fn append(&self, value: String, args: &mut args::Args) -> anyhow::Result<()> {
+        let spec = value.parse()?;
+
+        args.colors.push(spec);
+        Ok(())
+    }
+}
+
+/// -j/--threads
+#[derive(Debug)]
+struct Threads;
+
+impl Flag for Threads {
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    fn name_short(&self) -> Option<u8> {
+        Some(b'j')
+    }
+
+    fn name_long(&self) -> &'static str {
+        "threads"
+    }
+
+    fn doc_short(&self) -> &'static str {
+        r"Number of threads to search with, 0 picks one automatically"
+    }
+
+    fn _doc_long(&self) -> &'static str {
+        ""
+    }
+
+    fn update(&self, value: FlagValue<bool, String>, args: &mut args::Args) -> anyhow::Result<()> {
+        let threads = value.unwrap_value();
+
+        args.threads = threads
+            .parse()
+            .with_context(|| format!("invalid number of threads '{threads}'"))?;
+        Ok(())
+    }
+}
+
+use crate::parse::{ParseResult, Parser};
+pub fn parse() -> ParseResult<args::Args> {
+    let parser = Parser::new();
+    let mut args = args::Args::default();
+
+    if let Err(err) = parser.parse(std::env::args().skip(1), &mut args) {
+        return ParseResult::Err(err);
+    };
+
+    // --type-add may come after --type-list, so the types to list are only
+    // known once every flag has been parsed
+    if let Some(args::SpecialMode::TypeList(type_defs)) = &mut args.special {
+        type_defs.clone_from(&args.type_defs);
+    }
 
     // we can bail early if a special mode was enabled.
     // This is basically only for version and help output
@@ -1,12 +1,30 @@
 use std::{
-    io::{Read, Write},
+    io::Write,
+    path::{Path, PathBuf},
     process::ExitCode,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
 };
 
+use messages::err_message;
+use termcolor::WriteColor;
+use walk::WalkState;
+
 mod args;
+mod color;
 mod docs;
 mod flags;
+mod matcher;
+mod messages;
 mod parse;
+mod printer;
+mod rewrite;
+mod searcher;
+mod types;
+mod walk;
 
 fn main() -> ExitCode {
     let args = flags::parse();
@@ -15,12 +33,16 @@ fn main() -> ExitCode {
         Ok(exit_code) => exit_code,
         Err(err) => {
             eprintln!("error: {:#}", err);
-            ExitCode::from(1)
+            ExitCode::from(2)
         }
     }
 }
 
 /// The main entry point for minigerp
+///
+/// Following grep, the exit code is 0 if something matched, 1 if nothing
+/// matched and 2 if an error occurred. In quiet mode a match wins over an
+/// error, since the search stops at the first match anyway.
 fn run(args: parse::ParseResult<args::Args>) -> anyhow::Result<ExitCode> {
     use args::Mode;
     use parse::ParseResult;
@@ -32,38 +54,247 @@ fn run(args: parse::ParseResult<args::Args>) -> anyhow::Result<ExitCode> {
     };
 
     let matched = match args.mode {
-        Mode::Search(_) if !args.matches_possible() => false,
-        Mode::Search(mode) => search(&mut args, mode)?,
+        _ if !args.matches_possible() => false,
+        Mode::Search(mode)
+        | Mode::Count(mode)
+        | Mode::CountMatches(mode)
+        | Mode::FilesWithMatches(mode)
+        | Mode::FilesWithoutMatch(mode)
+        | Mode::Rewrite(mode) => search(&mut args, mode)?,
     };
 
-    Ok(if matched {
+    Ok(if matched && (args.quiet || !messages::errored()) {
         ExitCode::from(0)
+    } else if messages::errored() {
+        ExitCode::from(2)
     } else {
-        anyhow::bail!("pattern not matched in the file")
+        ExitCode::from(1)
     })
 }
 
-fn search(args: &mut args::Args, _mode: args::Searchmode) -> anyhow::Result<bool> {
-    let file = args.get_file()?;
-    let patterns = args.get_patterns()?;
+fn search(args: &mut args::Args, mode: args::Searchmode) -> anyhow::Result<bool> {
+    use args::{Mode, OutputMode};
 
-    // print the file name
-    println!("{}", file);
+    let output_flag = match args.output {
+        OutputMode::Standard => None,
+        OutputMode::Json => Some("--json"),
+        OutputMode::Vimgrep => Some("--vimgrep"),
+    };
+    if let Some(flag) = output_flag {
+        anyhow::ensure!(
+            matches!(args.mode, Mode::Search(_)),
+            "{flag} can't be used with -c/--count, --count-matches, -l/--files-with-matches, \
+             --files-without-match or --rewrite"
+        );
+    }
 
-    let mut file = std::fs::File::open(file)?;
+    match args.mode {
+        Mode::Rewrite(_) => {
+            anyhow::ensure!(args.replace.is_some(), "--rewrite requires -r/--replace");
+            anyhow::ensure!(
+                !args.invert,
+                "--rewrite can't be used with -v/--invert-match"
+            );
+            anyhow::ensure!(!args.quiet, "--rewrite can't be used with -q/--quiet");
+        }
+        _ => anyhow::ensure!(
+            !args.dry_run && !args.backup,
+            "--dry-run and --backup can only be used with --rewrite"
+        ),
+    }
 
-    let mut file_content = String::new();
-    file.read_to_string(&mut file_content)?;
+    // patterns are read from stdin only once it is known not to be searched.
+    // They come from flags then, so every positional is a path and the paths
+    // can be taken first.
+    let (patterns, paths) = match args.patterns_from_stdin {
+        true => {
+            let paths = args.get_paths()?;
+            anyhow::ensure!(
+                !paths.iter().any(|path| path == Path::new(args::STDIN)),
+                "-F/--pattern-file can't read patterns from standard input while it is searched, \
+                 give the files to search"
+            );
+            (args.get_patterns()?, paths)
+        }
+        false => (args.get_patterns()?, args.get_paths()?),
+    };
+
+    if let Mode::Rewrite(_) = args.mode {
+        anyhow::ensure!(
+            !paths.iter().any(|path| path == Path::new(args::STDIN)),
+            "--rewrite can't rewrite standard input, give the files to rewrite"
+        );
+    }
+
+    // build the matcher up front so that an invalid pattern is reported
+    // before anything is printed
+    let matcher_config = matcher::Config {
+        mode,
+        case: args.case,
+        word: args.word,
+        line: args.line,
+        replace: args.replace.is_some(),
+    };
+    let matcher = matcher::new(&matcher_config, &patterns)?;
 
-    for pattern in &patterns {
-        for (index, line) in file_content.lines().enumerate() {
-            if line.contains(pattern) {
-                println!("{}: {}", index + 1, line);
+    let specs = color::ColorSpecs::new(&args.colors);
+    let config = searcher::Config {
+        mode: args.mode,
+        binary: args.binary,
+        invert: args.invert,
+        only_matching: args.only_matching,
+        byte_offset: args.byte_offset,
+        before_context: args.get_before_context(),
+        after_context: args.get_after_context(),
+        stats: matches!(args.output, OutputMode::Json),
+        quiet: args.quiet,
+    };
+    let replacement = args.replace.as_deref().map(str::as_bytes);
+    let searcher = searcher::Searcher::new(&config, matcher.as_ref());
+
+    let start = Instant::now();
+    let matched = AtomicBool::new(false);
+    let stats = Mutex::new(searcher::Stats::default());
+
+    let types = types::Types::new(&args.type_defs)?;
+
+    let walk_config = walk::Config {
+        threads: args.get_threads(),
+        hidden: args.hidden,
+        ignore: !args.no_ignore,
+        globs: walk::build_globs(&args.globs)?,
+        types: types.matcher(&args.types)?,
+    };
+
+    // output is only buffered per file when several files may be searched
+    // at once, otherwise it is written as it is found
+    let direct = walk_config.threads == 1
+        || matches!(&paths[..], [path] if path == Path::new(args::STDIN) || path.is_file());
+    let output = printer::Output::new(args.get_color_choice(), direct);
+
+    let visit = |path: &Path, explicit| {
+        let mut stdout = output.direct();
+        let buffered = stdout.is_none();
+        let mut buffer = output.buffer();
+        let out: &mut dyn WriteColor = match stdout.as_deref_mut() {
+            Some(stdout) => stdout,
+            None => &mut buffer,
+        };
+
+        let mut sink: Box<dyn searcher::Sink + '_> = match (args.output, args.mode) {
+            _ if args.quiet => Box::new(printer::Null),
+            (OutputMode::Json, _) => Box::new(printer::Json::new(
+                &mut *out,
+                &config,
+                matcher.as_ref(),
+                replacement,
+            )),
+            (OutputMode::Vimgrep, _) => Box::new(printer::Vimgrep::new(
+                &mut *out,
+                &specs,
+                &config,
+                matcher.as_ref(),
+                replacement,
+            )),
+            (OutputMode::Standard, Mode::Search(_)) => Box::new(printer::Standard::new(
+                &mut *out,
+                &specs,
+                &config,
+                matcher.as_ref(),
+                replacement,
+            )),
+            (OutputMode::Standard, Mode::Rewrite(_)) => Box::new(rewrite::Rewriter::new(
+                &mut *out,
+                &specs,
+                matcher.as_ref(),
+                replacement.unwrap_or_default(),
+                args.dry_run,
+                args.backup,
+            )),
+            (OutputMode::Standard, _) => {
+                Box::new(printer::Summary::new(&mut *out, &specs, &config))
             }
+        };
+
+        let result = searcher.search_path(path, explicit, sink.as_mut());
+        drop(sink);
+        drop(stdout);
+
+        match result {
+            Ok(Some(file_stats)) => {
+                stats.lock().unwrap().add(&file_stats);
+
+                let file_matched = match config.mode {
+                    Mode::FilesWithoutMatch(_) => file_stats.matched_lines == 0,
+                    _ => file_stats.matched_lines > 0,
+                };
+                if !file_matched {
+                    return (WalkState::Continue, None);
+                }
+
+                matched.store(true, Ordering::Relaxed);
+
+                // in quiet mode, the first match is all there is to know
+                if args.quiet {
+                    return (WalkState::Quit, None);
+                }
+
+                // nothing is left to print if the output went straight to
+                // stdout
+                if buffered {
+                    return (WalkState::Continue, Some((path.to_path_buf(), buffer)));
+                }
+            }
+            Ok(None) => {}
+            // a closed pipe, e.g., when piping into `head`, is not worth
+            // reporting
+            Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => {
+                return (WalkState::Quit, None);
+            }
+            Err(err) => {
+                err_message!("{}: {}", printer::display_path(path), err);
+            }
+        }
+
+        (WalkState::Continue, None)
+    };
+
+    // the buffered output of files is printed in the order of the paths
+    // given, see `walk::walk`
+    let emit = |(path, buffer): (PathBuf, termcolor::Buffer)| {
+        if let Err(err) = output.print(&buffer) {
+            if err.kind() == std::io::ErrorKind::BrokenPipe {
+                return WalkState::Quit;
+            }
+            err_message!("{}: {}", printer::display_path(&path), err);
         }
+
+        WalkState::Continue
+    };
+
+    walk::walk(paths, &walk_config, visit, emit);
+
+    if let OutputMode::Json = args.output
+        && !args.quiet
+    {
+        let mut out = output.buffer();
+        printer::Json::new(&mut out, &config, matcher.as_ref(), replacement)
+            .summary(&stats.lock().unwrap(), start.elapsed())?;
+        output.print(&out).or_else(ignore_broken_pipe)?;
     }
 
-    Ok(true)
+    output.flush().or_else(ignore_broken_pipe)?;
+
+    Ok(matched.load(Ordering::Relaxed))
+}
+
+/// Turns a closed pipe into success, since the reader going away early,
+/// e.g., `head`, is not worth reporting.
+fn ignore_broken_pipe(err: std::io::Error) -> std::io::Result<()> {
+    match err.kind() {
+        std::io::ErrorKind::BrokenPipe => Ok(()),
+        _ => Err(err),
+    }
 }
 
 /// Implements minigrep's "special" modes.
@@ -77,6 +308,7 @@ pub fn special(special_mode: args::SpecialMode) -> anyhow::Result<ExitCode> {
         SpecialMode::HelpLong => docs::generate_help_long(),
         SpecialMode::VersionShort => docs::generate_version_short(),
         SpecialMode::VersionLong => docs::generate_version_long(),
+        SpecialMode::TypeList(type_defs) => types::Types::new(&type_defs)?.list(),
     };
 
     writeln!(std::io::stdout(), "{}", output)?;
@@ -0,0 +1,431 @@
+use std::ops::Range;
+
+use anyhow::Context;
+
+use crate::args::{CaseMode, Searchmode};
+
+/// A matcher reports whether a line of text contains any of the patterns.
+///
+/// Every search mode provides its own implementation, so `search` never has
+/// to care about how a pattern is actually matched. All patterns are matched
+/// in a single pass over the line.
+pub trait Matcher: std::fmt::Debug + Send + Sync {
+    /// Returns true if the given line matches.
+    ///
+    /// Lines are arbitrary bytes, they don't need to be valid UTF-8.
+    fn is_match(&self, line: &[u8]) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+
+    /// Returns the span of the leftmost match in `line` that starts at or
+    /// after `start`.
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>>;
+
+    /// Returns the spans of all non-overlapping, non-empty matches in `line`.
+    fn find_all(&self, line: &[u8]) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+
+        while start <= line.len() {
+            let Some(span) = self.find_at(line, start) else {
+                break;
+            };
+
+            // an empty match has nothing to show, skip over it
+            if span.is_empty() {
+                start = span.end + 1;
+                continue;
+            }
+
+            start = span.end;
+            spans.push(span);
+        }
+
+        spans
+    }
+
+    /// Appends what the match at `span` in `line` is replaced by to `dst`.
+    ///
+    /// Regex matchers expand references to capture groups in `replacement`,
+    /// e.g., `$1` or `${name}`, other matchers append it as is.
+    fn replace(&self, _line: &[u8], _span: Range<usize>, replacement: &[u8], dst: &mut Vec<u8>) {
+        dst.extend_from_slice(replacement);
+    }
+}
+
+/// The options a matcher is built with.
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub mode: Searchmode,
+    pub case: CaseMode,
+    /// Whether matches must be whole words.
+    pub word: bool,
+    /// Whether matches must be whole lines. This wins over `word`.
+    pub line: bool,
+    /// Whether matches are replaced, which for regexes needs every pattern
+    /// on its own to expand capture groups with.
+    pub replace: bool,
+}
+
+impl Config {
+    /// Returns true if `patterns` should be matched case insensitively.
+    fn is_case_insensitive(&self, patterns: &[String]) -> bool {
+        match self.case {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !patterns
+                .iter()
+                .any(|pattern| has_uppercase_literal(self.mode, pattern)),
+        }
+    }
+
+    /// Returns what a match must be surrounded by.
+    fn boundary(&self) -> Boundary {
+        match (self.word, self.line) {
+            (_, true) => Boundary::Line,
+            (true, false) => Boundary::Word,
+            (false, false) => Boundary::None,
+        }
+    }
+}
+
+/// What a match must be surrounded by.
+#[derive(Debug, Clone, Copy)]
+pub enum Boundary {
+    /// A match may be anywhere.
+    None,
+    /// A match must not be preceded or followed by a word character.
+    Word,
+    /// A match must span the whole line.
+    Line,
+}
+
+impl Boundary {
+    /// Returns `regex` wrapped in what its matches must be surrounded by.
+    fn wrap(self, regex: &str) -> String {
+        // the half word boundaries only look at the outside of the match, so
+        // a pattern that starts or ends with a non-word character still
+        // matches as a whole word
+        match self {
+            Boundary::None => regex.to_string(),
+            Boundary::Word => format!(r"\b{{start-half}}(?:{regex})\b{{end-half}}"),
+            Boundary::Line => format!("^(?:{regex})$"),
+        }
+    }
+}
+
+/// Builds the matcher for `patterns` according to the given config.
+///
+/// This fails if a pattern is not valid for the mode, e.g., a regex with an
+/// unclosed group.
+pub fn new(config: &Config, patterns: &[String]) -> anyhow::Result<Box<dyn Matcher>> {
+    let case_insensitive = config.is_case_insensitive(patterns);
+
+    let boundary = config.boundary();
+
+    let matcher: Box<dyn Matcher> = match config.mode {
+        // no patterns match nothing, whereas an empty regex would match
+        // everywhere
+        _ if patterns.is_empty() => Box::new(LiteralMatcher::new(patterns)?),
+        Searchmode::Standard if !case_insensitive && matches!(boundary, Boundary::None) => {
+            Box::new(LiteralMatcher::new(patterns)?)
+        }
+        // Aho-Corasick only folds ASCII case and knows nothing about word
+        // boundaries, so literals are searched as escaped regexes instead
+        Searchmode::Standard => Box::new(RegexMatcher::new_literal(
+            patterns,
+            case_insensitive,
+            boundary,
+        )?),
+        Searchmode::Regex => Box::new(RegexMatcher::new(
+            patterns,
+            case_insensitive,
+            boundary,
+            config.replace,
+        )?),
+    };
+
+    Ok(matcher)
+}
+
+/// Returns true if `pattern` contains an uppercase letter that is matched
+/// literally.
+///
+/// For regexes, escape sequences such as `\S` or `\W` are not literals and
+/// are skipped.
+fn has_uppercase_literal(mode: Searchmode, pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+
+    while let Some(ch) = chars.next() {
+        if matches!(mode, Searchmode::Regex) && ch == '\\' {
+            chars.next();
+            continue;
+        }
+
+        if ch.is_uppercase() {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Matches any of a set of fixed strings anywhere in the line.
+///
+/// The strings are compiled into a single Aho-Corasick automaton, so a line
+/// is scanned once no matter how many patterns there are.
+#[derive(Debug)]
+pub struct LiteralMatcher {
+    automaton: aho_corasick::AhoCorasick,
+}
+
+impl LiteralMatcher {
+    pub fn new(literals: &[String]) -> anyhow::Result<LiteralMatcher> {
+        let automaton = aho_corasick::AhoCorasick::builder()
+            .match_kind(aho_corasick::MatchKind::LeftmostFirst)
+            .build(literals)
+            .context("failed to build matcher for patterns")?;
+
+        Ok(LiteralMatcher { automaton })
+    }
+}
+
+impl Matcher for LiteralMatcher {
+    fn is_match(&self, line: &[u8]) -> bool {
+        self.automaton.is_match(line)
+    }
+
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        let input = aho_corasick::Input::new(line).span(start..line.len());
+        self.automaton.find(input).map(|mat| mat.range())
+    }
+}
+
+/// Matches any of a set of regular expressions anywhere in the line.
+///
+/// The expressions are joined into a single alternation, so a line is
+/// scanned once no matter how many patterns there are.
+#[derive(Debug)]
+pub struct RegexMatcher {
+    regex: regex::bytes::Regex,
+    /// Every pattern on its own, to expand the capture groups of the pattern
+    /// that matched in a replacement. Empty for fixed strings, and when
+    /// nothing is replaced.
+    regexes: Vec<regex::bytes::Regex>,
+    /// Whether the patterns are fixed strings, which have no capture groups
+    /// to refer to in a replacement.
+    literal: bool,
+}
+
+impl RegexMatcher {
+    /// Builds a matcher for regexes. The patterns are only kept on their own
+    /// as well if `replace` is set.
+    pub fn new(
+        patterns: &[String],
+        case_insensitive: bool,
+        boundary: Boundary,
+        replace: bool,
+    ) -> anyhow::Result<RegexMatcher> {
+        let build = |regex: &str| build(regex, case_insensitive);
+
+        let mut regexes = Vec::new();
+        if replace {
+            for pattern in patterns {
+                let regex = build(&boundary.wrap(pattern))
+                    .with_context(|| format!("failed to parse regex pattern '{pattern}'"))?;
+                regexes.push(regex);
+            }
+        }
+
+        // the combined expression is only used to find matches, its capture
+        // groups are never looked at, and patterns are free to use the same
+        // group names. Parsing every pattern on its own also makes an error
+        // point at the pattern that caused it.
+        let joined = patterns
+            .iter()
+            .map(|pattern| Ok(format!("(?:{})", unname_groups(pattern)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .join("|");
+
+        let regex = build(&boundary.wrap(&joined)).context("failed to combine regex patterns")?;
+
+        Ok(RegexMatcher {
+            regex,
+            regexes,
+            literal: false,
+        })
+    }
+
+    /// Builds a matcher for fixed strings, by escaping them.
+    pub fn new_literal(
+        literals: &[String],
+        case_insensitive: bool,
+        boundary: Boundary,
+    ) -> anyhow::Result<RegexMatcher> {
+        let escaped = literals
+            .iter()
+            .map(|literal| regex::escape(literal))
+            .collect::<Vec<_>>()
+            .join("|");
+
+        let regex = build(&boundary.wrap(&escaped), case_insensitive)
+            .context("failed to build matcher for patterns")?;
+
+        Ok(RegexMatcher {
+            regex,
+            regexes: Vec::new(),
+            literal: true,
+        })
+    }
+}
+
+/// Builds a regex, matching case insensitively if asked to.
+fn build(regex: &str, case_insensitive: bool) -> Result<regex::bytes::Regex, regex::Error> {
+    regex::bytes::RegexBuilder::new(regex)
+        .case_insensitive(case_insensitive)
+        .build()
+}
+
+/// Returns `pattern` with its named capture groups turned into unnamed ones.
+fn unname_groups(pattern: &str) -> anyhow::Result<String> {
+    use regex_syntax::ast::{Ast, GroupKind};
+
+    fn visit(ast: &mut Ast) {
+        match ast {
+            Ast::Group(group) => {
+                if let GroupKind::CaptureName { .. } = group.kind {
+                    group.kind = GroupKind::CaptureIndex(0);
+                }
+                visit(&mut group.ast);
+            }
+            Ast::Repetition(repetition) => visit(&mut repetition.ast),
+            Ast::Alternation(alternation) => alternation.asts.iter_mut().for_each(visit),
+            Ast::Concat(concat) => concat.asts.iter_mut().for_each(visit),
+            _ => {}
+        }
+    }
+
+    let mut ast = regex_syntax::ast::parse::Parser::new()
+        .parse(pattern)
+        .with_context(|| format!("failed to parse regex pattern '{pattern}'"))?;
+    visit(&mut ast);
+
+    let mut unnamed = String::with_capacity(pattern.len());
+    regex_syntax::ast::print::Printer::new().print(&ast, &mut unnamed)?;
+    Ok(unnamed)
+}
+
+impl Matcher for RegexMatcher {
+    fn is_match(&self, line: &[u8]) -> bool {
+        self.regex.is_match(line)
+    }
+
+    fn find_at(&self, line: &[u8], start: usize) -> Option<Range<usize>> {
+        self.regex.find_at(line, start).map(|mat| mat.range())
+    }
+
+    fn replace(&self, line: &[u8], span: Range<usize>, replacement: &[u8], dst: &mut Vec<u8>) {
+        if self.literal {
+            dst.extend_from_slice(replacement);
+            return;
+        }
+
+        // the alternation prefers the first pattern that matches at the start
+        // of the match, and searching from there with that pattern alone finds
+        // the same match again, this time with its own capture groups
+        let caps = self.regexes.iter().find_map(|regex| {
+            regex
+                .captures_at(line, span.start)
+                .filter(|caps| caps.get(0).is_some_and(|mat| mat.start() == span.start))
+        });
+
+        match caps {
+            Some(caps) => caps.expand(replacement, dst),
+            None => dst.extend_from_slice(replacement),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(
+        mode: Searchmode,
+        case: CaseMode,
+        word: bool,
+        patterns: &[&str],
+    ) -> Box<dyn Matcher> {
+        let config = Config {
+            mode,
+            case,
+            word,
+            line: false,
+            replace: true,
+        };
+        let patterns = patterns
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect::<Vec<_>>();
+        new(&config, &patterns).unwrap()
+    }
+
+    /// Returns the matches in `line`.
+    fn found<'a>(matcher: &dyn Matcher, line: &'a str) -> Vec<&'a str> {
+        matcher
+            .find_all(line.as_bytes())
+            .into_iter()
+            .map(|span| &line[span])
+            .collect()
+    }
+
+    /// Returns every match in `line` replaced by `replacement`.
+    fn replaced(matcher: &dyn Matcher, line: &str, replacement: &str) -> String {
+        let mut dst = Vec::new();
+        let mut last = 0;
+        for span in matcher.find_all(line.as_bytes()) {
+            dst.extend_from_slice(&line.as_bytes()[last..span.start]);
+            last = span.end;
+            matcher.replace(line.as_bytes(), span, replacement.as_bytes(), &mut dst);
+        }
+        dst.extend_from_slice(&line.as_bytes()[last..]);
+
+        String::from_utf8(dst).unwrap()
+    }
+
+    #[test]
+    fn replacement_uses_the_groups_of_the_pattern_that_matched() {
+        let matcher = matcher(
+            Searchmode::Regex,
+            CaseMode::Sensitive,
+            false,
+            &[r"(?P<n>a)\d", r"(?P<n>b)\d"],
+        );
+
+        assert_eq!(replaced(matcher.as_ref(), "a1 b2 c3", "<$n>"), "<a> <b> c3");
+    }
+
+    #[test]
+    fn words_only_need_a_boundary_outside_of_the_match() {
+        for mode in [Searchmode::Standard, Searchmode::Regex] {
+            let matcher = matcher(mode, CaseMode::Sensitive, true, &["-foo", "bar"]);
+
+            assert_eq!(found(matcher.as_ref(), "x -foo bar"), ["-foo", "bar"]);
+            assert_eq!(found(matcher.as_ref(), "x-foo -foox"), Vec::<&str>::new());
+            assert_eq!(found(matcher.as_ref(), "barx xbar (bar)"), ["bar"]);
+        }
+    }
+
+    #[test]
+    fn smart_case_ignores_escape_sequences() {
+        let insensitive = matcher(Searchmode::Regex, CaseMode::Smart, false, &[r"\Sfoo"]);
+        assert_eq!(found(insensitive.as_ref(), "xfoo XFOO"), ["xfoo", "XFOO"]);
+
+        let sensitive = matcher(Searchmode::Regex, CaseMode::Smart, false, &[r"\sFoo"]);
+        assert_eq!(found(sensitive.as_ref(), " foo Foo"), [" Foo"]);
+
+        // fixed strings have no escape sequences
+        let sensitive = matcher(Searchmode::Standard, CaseMode::Smart, false, &[r"\Sfoo"]);
+        assert_eq!(found(sensitive.as_ref(), r"\sfoo \Sfoo"), [r"\Sfoo"]);
+    }
+}
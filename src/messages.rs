@@ -0,0 +1,27 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ERRORED: AtomicBool = AtomicBool::new(false);
+
+/// Emit a non-fatal error message to stderr and remember that an error
+/// occurred, which decides minigrep's exit code.
+///
+/// Searching carries on after a non-fatal error, e.g., a file that could not
+/// be read in a directory that is being searched.
+macro_rules! err_message {
+    ($($tt:tt)*) => {
+        crate::messages::set_errored();
+        eprintln!("error: {}", std::format_args!($($tt)*));
+    }
+}
+
+pub(crate) use err_message;
+
+/// Returns true if a non-fatal error has been emitted.
+pub fn errored() -> bool {
+    ERRORED.load(Ordering::Relaxed)
+}
+
+/// Remember that a non-fatal error has been emitted.
+pub fn set_errored() {
+    ERRORED.store(true, Ordering::Relaxed);
+}
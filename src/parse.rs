@@ -30,6 +30,12 @@ impl Parser {
             let mut infos = Vec::new();
 
             for &flag in FLAGS.iter() {
+                assert!(
+                    !(flag.is_switch() && flag.is_multivalued()),
+                    "--{} is a multivalued switch",
+                    flag.name_long()
+                );
+
                 // flag with name_long
                 infos.push(FlagInfo {
                     flag,
@@ -69,27 +75,27 @@ impl Parser {
                     };
                     continue;
                 }
-                lexopt::Arg::Short(ch) if ch == 'h' => {
+                lexopt::Arg::Short('h') => {
                     // Special case -h/--help since behavior is different
                     // based on whether short or long flag is given.
                     args.special = Some(args::SpecialMode::HelpShort);
                     continue;
                 }
-                lexopt::Arg::Short(ch) if ch == 'v' => {
-                    // Special case -v/--version since behavior is different
+                lexopt::Arg::Short('V') => {
+                    // Special case -V/--version since behavior is different
                     // based on whether short or long flag is given.
                     args.special = Some(args::SpecialMode::VersionShort);
                     continue;
                 }
                 lexopt::Arg::Short(ch) => self.find_short(ch),
-                lexopt::Arg::Long(name) if name == "help" => {
+                lexopt::Arg::Long("help") => {
                     // Special case -h/--help since behavior is different
                     // based on whether short or long flag is given.
                     args.special = Some(args::SpecialMode::HelpLong);
                     continue;
                 }
-                lexopt::Arg::Long(name) if name == "version" => {
-                    // Special case -v/--version since behavior is different
+                lexopt::Arg::Long("version") => {
+                    // Special case -V/--version since behavior is different
                     // based on whether short or long flag is given.
                     args.special = Some(args::SpecialMode::VersionLong);
                     continue;
@@ -103,7 +109,9 @@ impl Parser {
                 FlagLookUp::UnrecognizedLong(str) => anyhow::bail!("Unrecognized flag --{str}"),
             };
 
-            // TODO: handel for multivalued flag
+            // Each occurrence of a flag carries exactly one value, which is
+            // handed to the flag right away, so that flags adding to the same
+            // arguments keep the order they were given in.
             let value: FlagValue<bool, String> = if mat.flag.is_switch() {
                 FlagValue::Switch(true)
             } else {
@@ -114,9 +122,11 @@ impl Parser {
                 .with_context(|| format!("for flag : -{:?} | --{:?}", mat.name, mat.name))?
             };
 
-            mat.flag
-                .update(value, args)
-                .with_context(|| format!("error parsing flag {:?}", mat))?;
+            match mat.flag.is_multivalued() {
+                true => mat.flag.append(value.unwrap_value(), args),
+                false => mat.flag.update(value, args),
+            }
+            .with_context(|| format!("error parsing flag {:?}", mat))?;
         }
 
         Ok(ExitCode::from(0))
@@ -144,3 +154,58 @@ impl Parser {
         FlagLookUp::Match(&self.infos[index])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(rawargs: &[&str]) -> args::Args {
+        let mut args = args::Args::default();
+        Parser::new().parse(rawargs, &mut args).unwrap();
+        args
+    }
+
+    #[test]
+    fn multivalued_flags_append() {
+        let mut args = parse(&[
+            "-p",
+            "foo",
+            "--pattern",
+            "bar",
+            "-g",
+            "*.rs",
+            "--iglob",
+            "*.md",
+        ]);
+
+        assert_eq!(args.get_patterns().unwrap(), ["foo", "bar"]);
+        assert_eq!(
+            args.globs.iter().map(|glob| &glob.glob).collect::<Vec<_>>(),
+            ["*.rs", "*.md"]
+        );
+    }
+
+    #[test]
+    fn other_flags_overwrite() {
+        let args = parse(&["-A", "1", "--after-context", "2", "-r", "x", "-r", "y"]);
+
+        assert_eq!(args.after_context, Some(2));
+        assert_eq!(args.replace.as_deref(), Some("y"));
+    }
+
+    #[test]
+    fn pattern_files_are_read_once_parsed() {
+        let path = std::env::temp_dir().join(format!("minigrep-parse-{}", std::process::id()));
+        std::fs::write(&path, "b\n\n# comment\n\\#c\n").unwrap();
+
+        let mut args = parse(&["-p", "a", "-F", path.to_str().unwrap(), "-p", "d"]);
+        let patterns = args.get_patterns();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(patterns.unwrap(), ["a", "b", "#c", "d"]);
+
+        // stdin is left alone while parsing, there may be nothing to read
+        let args = parse(&["-F", "-", "-h"]);
+        assert!(args.patterns_from_stdin);
+        assert!(matches!(args.special, Some(args::SpecialMode::HelpShort)));
+    }
+}
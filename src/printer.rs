@@ -0,0 +1,725 @@
+use std::borrow::Cow;
+use std::io::{self, IsTerminal, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use base64::Engine;
+use serde_json::json;
+use termcolor::WriteColor;
+
+use crate::args::{self, Mode};
+use crate::color::ColorSpecs;
+use crate::matcher::Matcher;
+use crate::searcher::{self, Line, Sink, Stats};
+
+/// Where the output of every file ends up, shared by all worker threads.
+///
+/// When files are searched one at a time, their output is written straight
+/// to stdout as it is found, so that matches show up right away and memory
+/// use doesn't grow with the number of matches. Stdout is then buffered, to
+/// write large chunks at a time, except when it is a terminal and output is
+/// expected as soon as it is found.
+///
+/// Otherwise, each file is printed into a buffer of its own, which is then
+/// written to stdout in one go, so that the output of different files never
+/// interleaves.
+///
+/// Either way, colors are written the way the color choice asks for, i.e.,
+/// with the console API on Windows consoles, unless ANSI escape sequences
+/// are asked for.
+pub struct Output {
+    buffers: termcolor::BufferWriter,
+    /// Stdout to write to directly, when files are searched one at a time.
+    direct: Option<Mutex<Box<dyn WriteColor + Send>>>,
+}
+
+impl Output {
+    /// With `direct` set, files are expected to be searched one at a time and
+    /// written to `Output::direct`.
+    pub fn new(color: termcolor::ColorChoice, direct: bool) -> Output {
+        let direct = direct.then(|| {
+            let stdout: Box<dyn WriteColor + Send> = match io::stdout().is_terminal() {
+                // line buffered
+                true => Box::new(termcolor::StandardStream::stdout(color)),
+                false => Box::new(termcolor::BufferedStandardStream::stdout(color)),
+            };
+            Mutex::new(stdout)
+        });
+
+        Output {
+            buffers: termcolor::BufferWriter::stdout(color),
+            direct,
+        }
+    }
+
+    /// Returns stdout to write a file to as it is searched, unless files are
+    /// printed into buffers.
+    pub fn direct(&self) -> Option<MutexGuard<'_, Box<dyn WriteColor + Send>>> {
+        self.direct.as_ref().map(|stdout| stdout.lock().unwrap())
+    }
+
+    /// Returns an empty buffer to print a file into.
+    pub fn buffer(&self) -> termcolor::Buffer {
+        self.buffers.buffer()
+    }
+
+    /// Writes `buf` to stdout.
+    pub fn print(&self, buf: &termcolor::Buffer) -> io::Result<()> {
+        // with direct output, only what isn't tied to a file is buffered,
+        // i.e., the JSON summary, which has no colors
+        if let Some(mut direct) = self.direct() {
+            return direct.write_all(buf.as_slice());
+        }
+
+        self.buffers.print(buf)
+    }
+
+    /// Writes out everything still buffered.
+    pub fn flush(&self) -> io::Result<()> {
+        match self.direct() {
+            Some(mut direct) => direct.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Debug for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Output")
+            .field("buffers", &self.buffers)
+            .field("direct", &self.direct.is_some())
+            .finish()
+    }
+}
+
+/// Returns the name `path` is displayed with, i.e., a label for stdin.
+pub fn display_path(path: &Path) -> Cow<'_, str> {
+    if path == Path::new(args::STDIN) {
+        return args::STDIN_LABEL.into();
+    }
+
+    path.to_string_lossy()
+}
+
+/// The standard, human readable, output format.
+///
+/// The path of a file is written as a heading above its matching lines, and
+/// each line is prefixed by its line number.
+#[derive(Debug)]
+pub struct Standard<'a, W> {
+    out: W,
+    specs: &'a ColorSpecs,
+    config: &'a searcher::Config,
+    matcher: &'a dyn Matcher,
+    /// What matches are replaced by in the output, if anything.
+    replacement: Option<&'a [u8]>,
+    /// The path of the file being searched.
+    path: PathBuf,
+    /// Whether the heading for the current file has been written.
+    heading: bool,
+}
+
+impl<'a, W: WriteColor> Standard<'a, W> {
+    pub fn new(
+        out: W,
+        specs: &'a ColorSpecs,
+        config: &'a searcher::Config,
+        matcher: &'a dyn Matcher,
+        replacement: Option<&'a [u8]>,
+    ) -> Standard<'a, W> {
+        Standard {
+            out,
+            specs,
+            config,
+            matcher,
+            replacement,
+            path: PathBuf::new(),
+            heading: false,
+        }
+    }
+
+    /// Writes the path of the current file, styled as a path.
+    fn write_path(&mut self) -> io::Result<()> {
+        self.out.set_color(&self.specs.path)?;
+        write!(self.out, "{}", display_path(&self.path))?;
+        self.out.reset()
+    }
+
+    /// Writes the heading of the current file before its first line.
+    fn write_heading(&mut self) -> io::Result<()> {
+        if self.heading {
+            return Ok(());
+        }
+
+        self.heading = true;
+        self.write_path()?;
+        writeln!(self.out)
+    }
+
+    /// Writes what every line of output starts with, the line number and,
+    /// when asked for, the byte offset in the input.
+    ///
+    /// The separator after them tells matching lines (`:`) from context
+    /// lines (`-`) apart.
+    fn write_prefix(&mut self, line_number: u64, offset: u64, separator: u8) -> io::Result<()> {
+        self.out.set_color(&self.specs.line)?;
+        write!(self.out, "{}", line_number)?;
+        self.out.reset()?;
+
+        if self.config.byte_offset {
+            self.out.write_all(&[separator])?;
+            write!(self.out, "{}", offset)?;
+        }
+
+        self.out.write_all(&[separator, b' '])
+    }
+}
+
+impl<W: WriteColor> Sink for Standard<'_, W> {
+    fn begin(&mut self, path: &Path) -> io::Result<()> {
+        // the heading is only written once there is something to show
+        path.clone_into(&mut self.path);
+        self.heading = false;
+        Ok(())
+    }
+
+    fn matched(&mut self, line_number: u64, line: &Line<'_>) -> io::Result<()> {
+        // an inverted match is a line without any match, so there is nothing
+        // to highlight, and nothing to print with only-matching
+        if self.config.invert {
+            if !self.config.only_matching {
+                self.write_heading()?;
+                self.write_prefix(line_number, line.offset, b':')?;
+                self.out.write_all(line.bytes)?;
+                writeln!(self.out)?;
+            }
+            return Ok(());
+        }
+
+        if !self.config.only_matching {
+            self.write_heading()?;
+            self.write_prefix(line_number, line.offset, b':')?;
+            write_highlighted(
+                &mut self.out,
+                self.specs,
+                self.matcher,
+                self.replacement,
+                line.bytes,
+            )?;
+            return writeln!(self.out);
+        }
+
+        // the heading only goes with something to print under it
+        let spans = self.matcher.find_all(line.bytes);
+        if !spans.is_empty() {
+            self.write_heading()?;
+        }
+
+        for span in spans {
+            let offset = line.offset + span.start as u64;
+            self.write_prefix(line_number, offset, b':')?;
+            write_match(
+                &mut self.out,
+                self.specs,
+                self.matcher,
+                self.replacement,
+                line.bytes,
+                span,
+            )?;
+            writeln!(self.out)?;
+        }
+
+        Ok(())
+    }
+
+    fn context(&mut self, line_number: u64, offset: u64, line: &[u8]) -> io::Result<()> {
+        self.write_heading()?;
+        self.write_prefix(line_number, offset, b'-')?;
+        self.out.write_all(line)?;
+        writeln!(self.out)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        writeln!(self.out, "--")
+    }
+
+    fn end(&mut self, _: &Stats, binary_offset: Option<u64>) -> io::Result<()> {
+        // binary data is never printed, a single match is enough to say that
+        // the file matches
+        if let Some(offset) = binary_offset {
+            self.write_heading()?;
+            writeln!(
+                self.out,
+                "binary file matches (found \"\\0\" byte around offset {offset})"
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The output format of the modes that print a summary of each file rather
+/// than its lines, i.e., counts and lists of files.
+#[derive(Debug)]
+pub struct Summary<'a, W> {
+    out: W,
+    specs: &'a ColorSpecs,
+    config: &'a searcher::Config,
+    /// The path of the file being searched.
+    path: PathBuf,
+}
+
+impl<'a, W: WriteColor> Summary<'a, W> {
+    pub fn new(out: W, specs: &'a ColorSpecs, config: &'a searcher::Config) -> Summary<'a, W> {
+        Summary {
+            out,
+            specs,
+            config,
+            path: PathBuf::new(),
+        }
+    }
+
+    /// Writes the path of the current file, styled as a path.
+    fn write_path(&mut self) -> io::Result<()> {
+        self.out.set_color(&self.specs.path)?;
+        write!(self.out, "{}", display_path(&self.path))?;
+        self.out.reset()
+    }
+}
+
+impl<W: WriteColor> Sink for Summary<'_, W> {
+    fn begin(&mut self, path: &Path) -> io::Result<()> {
+        path.clone_into(&mut self.path);
+        Ok(())
+    }
+
+    fn matched(&mut self, _: u64, _: &Line<'_>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn context(&mut self, _: u64, _: u64, _: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn end(&mut self, stats: &Stats, _: Option<u64>) -> io::Result<()> {
+        match self.config.mode {
+            Mode::Count(_) | Mode::CountMatches(_) => {
+                let count = match self.config.mode {
+                    Mode::CountMatches(_) => stats.matches,
+                    _ => stats.matched_lines,
+                };
+
+                if count > 0 {
+                    self.write_path()?;
+                    writeln!(self.out, ":{}", count)?;
+                }
+            }
+            Mode::FilesWithMatches(_) | Mode::FilesWithoutMatch(_) => {
+                let listed = match self.config.mode {
+                    Mode::FilesWithMatches(_) => stats.matched_lines > 0,
+                    _ => stats.matched_lines == 0,
+                };
+
+                if listed {
+                    self.write_path()?;
+                    writeln!(self.out)?;
+                }
+            }
+            Mode::Search(_) | Mode::Rewrite(_) => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints nothing, for quiet mode, where the exit code is all the output.
+#[derive(Debug)]
+pub struct Null;
+
+impl Sink for Null {
+    fn begin(&mut self, _: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn matched(&mut self, _: u64, _: &Line<'_>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn context(&mut self, _: u64, _: u64, _: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn end(&mut self, _: &Stats, _: Option<u64>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The output format understood by editors, e.g., vim's `:grep`.
+///
+/// Every match is written on a line of its own as `path:line:column:text`,
+/// where the column is the 1-based byte column of the match. A line with
+/// several matches is written once for each of them.
+#[derive(Debug)]
+pub struct Vimgrep<'a, W> {
+    out: W,
+    specs: &'a ColorSpecs,
+    config: &'a searcher::Config,
+    matcher: &'a dyn Matcher,
+    /// What matches are replaced by in the output, if anything.
+    replacement: Option<&'a [u8]>,
+    /// The path of the file being searched.
+    path: PathBuf,
+}
+
+impl<'a, W: WriteColor> Vimgrep<'a, W> {
+    pub fn new(
+        out: W,
+        specs: &'a ColorSpecs,
+        config: &'a searcher::Config,
+        matcher: &'a dyn Matcher,
+        replacement: Option<&'a [u8]>,
+    ) -> Vimgrep<'a, W> {
+        Vimgrep {
+            out,
+            specs,
+            config,
+            matcher,
+            replacement,
+            path: PathBuf::new(),
+        }
+    }
+
+    /// Writes the path of the current file, styled as a path.
+    fn write_path(&mut self) -> io::Result<()> {
+        self.out.set_color(&self.specs.path)?;
+        write!(self.out, "{}", display_path(&self.path))?;
+        self.out.reset()
+    }
+
+    /// Writes the `path:line:column:` prefix of a match.
+    fn write_prefix(&mut self, line_number: u64, column: usize) -> io::Result<()> {
+        self.write_path()?;
+        self.out.write_all(b":")?;
+
+        self.out.set_color(&self.specs.line)?;
+        write!(self.out, "{}", line_number)?;
+        self.out.reset()?;
+
+        write!(self.out, ":{}:", column)
+    }
+}
+
+impl<W: WriteColor> Sink for Vimgrep<'_, W> {
+    fn begin(&mut self, path: &Path) -> io::Result<()> {
+        path.clone_into(&mut self.path);
+        Ok(())
+    }
+
+    fn matched(&mut self, line_number: u64, line: &Line<'_>) -> io::Result<()> {
+        // an inverted match is a line without any match, it is written once
+        // with the column of its start
+        if self.config.invert {
+            if !self.config.only_matching {
+                self.write_prefix(line_number, 1)?;
+                self.out.write_all(line.bytes)?;
+                writeln!(self.out)?;
+            }
+            return Ok(());
+        }
+
+        for span in self.matcher.find_all(line.bytes) {
+            self.write_prefix(line_number, span.start + 1)?;
+
+            match self.config.only_matching {
+                true => write_match(
+                    &mut self.out,
+                    self.specs,
+                    self.matcher,
+                    self.replacement,
+                    line.bytes,
+                    span,
+                )?,
+                false => write_highlighted(
+                    &mut self.out,
+                    self.specs,
+                    self.matcher,
+                    self.replacement,
+                    line.bytes,
+                )?,
+            }
+            writeln!(self.out)?;
+        }
+
+        Ok(())
+    }
+
+    fn context(&mut self, _: u64, _: u64, _: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn end(&mut self, _: &Stats, binary_offset: Option<u64>) -> io::Result<()> {
+        // binary data is never printed, as in the standard format
+        if let Some(offset) = binary_offset {
+            self.write_path()?;
+            writeln!(
+                self.out,
+                ": binary file matches (found \"\\0\" byte around offset {offset})"
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The JSON Lines output format, for other programs to consume.
+///
+/// Every event is written as a JSON object on a line of its own, with its
+/// kind under `type` and everything else under `data`. The `begin` and `end`
+/// events of a file are only written if something is written for the file
+/// in between, or if it matched.
+///
+/// Paths and lines are written as `{"text": ...}` when they are valid UTF-8,
+/// and as `{"bytes": ...}` with their base64 encoding otherwise. Lines are
+/// written without their line terminator. With a replacement, every
+/// submatch also carries what it is replaced by.
+#[derive(Debug)]
+pub struct Json<'a, W> {
+    out: W,
+    config: &'a searcher::Config,
+    matcher: &'a dyn Matcher,
+    /// What matches are replaced by, if anything.
+    replacement: Option<&'a [u8]>,
+    /// The path of the file being searched.
+    path: PathBuf,
+    /// Whether the `begin` event for the current file has been written.
+    begun: bool,
+}
+
+impl<'a, W: Write> Json<'a, W> {
+    pub fn new(
+        out: W,
+        config: &'a searcher::Config,
+        matcher: &'a dyn Matcher,
+        replacement: Option<&'a [u8]>,
+    ) -> Json<'a, W> {
+        Json {
+            out,
+            config,
+            matcher,
+            replacement,
+            path: PathBuf::new(),
+            begun: false,
+        }
+    }
+
+    /// Writes the `summary` event, with the statistics of every file searched
+    /// and the time the whole search took.
+    pub fn summary(&mut self, stats: &Stats, elapsed_total: Duration) -> io::Result<()> {
+        self.write_event(
+            "summary",
+            json!({
+                "elapsed_total": json_duration(elapsed_total),
+                "stats": json_stats(stats),
+            }),
+        )
+    }
+
+    /// Writes the `begin` event of the current file before its first line.
+    fn write_begin(&mut self) -> io::Result<()> {
+        if self.begun {
+            return Ok(());
+        }
+
+        self.begun = true;
+        self.write_event("begin", json!({ "path": json_path(&self.path) }))
+    }
+
+    /// Writes a `match` or `context` event for a line.
+    fn write_line(
+        &mut self,
+        kind: &str,
+        line_number: u64,
+        offset: u64,
+        line: &[u8],
+        submatches: Vec<serde_json::Value>,
+    ) -> io::Result<()> {
+        self.write_begin()?;
+        self.write_event(
+            kind,
+            json!({
+                "path": json_path(&self.path),
+                "lines": json_data(line),
+                "line_number": line_number,
+                "absolute_offset": offset,
+                "submatches": submatches,
+            }),
+        )
+    }
+
+    fn write_event(&mut self, kind: &str, data: serde_json::Value) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, &json!({ "type": kind, "data": data }))?;
+        writeln!(self.out)
+    }
+}
+
+impl<W: Write> Sink for Json<'_, W> {
+    fn begin(&mut self, path: &Path) -> io::Result<()> {
+        path.clone_into(&mut self.path);
+        self.begun = false;
+        Ok(())
+    }
+
+    fn matched(&mut self, line_number: u64, line: &Line<'_>) -> io::Result<()> {
+        // an inverted match is a line without any match
+        let submatches = match self.config.invert {
+            true => Vec::new(),
+            false => self
+                .matcher
+                .find_all(line.bytes)
+                .into_iter()
+                .map(|span| {
+                    let mut submatch = json!({
+                        "match": json_data(&line.bytes[span.clone()]),
+                        "start": span.start,
+                        "end": span.end,
+                    });
+
+                    if let Some(replacement) = self.replacement {
+                        let mut replaced = Vec::new();
+                        self.matcher
+                            .replace(line.bytes, span, replacement, &mut replaced);
+                        submatch["replacement"] = json_data(&replaced);
+                    }
+
+                    submatch
+                })
+                .collect(),
+        };
+
+        self.write_line("match", line_number, line.offset, line.bytes, submatches)
+    }
+
+    fn context(&mut self, line_number: u64, offset: u64, line: &[u8]) -> io::Result<()> {
+        self.write_line("context", line_number, offset, line, Vec::new())
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        // the line numbers already tell where the breaks are
+        Ok(())
+    }
+
+    fn end(&mut self, stats: &Stats, binary_offset: Option<u64>) -> io::Result<()> {
+        if stats.matched_lines > 0 {
+            self.write_begin()?;
+        }
+
+        if !self.begun {
+            return Ok(());
+        }
+
+        self.write_event(
+            "end",
+            json!({
+                "path": json_path(&self.path),
+                "binary_offset": binary_offset,
+                "stats": json_stats(stats),
+            }),
+        )
+    }
+}
+
+/// Writes `line` with every match in it highlighted, and replaced when
+/// there is a replacement.
+fn write_highlighted<W: WriteColor>(
+    out: &mut W,
+    specs: &ColorSpecs,
+    matcher: &dyn Matcher,
+    replacement: Option<&[u8]>,
+    line: &[u8],
+) -> io::Result<()> {
+    // finding every match is wasted work when there is nothing to show
+    if replacement.is_none() && !out.supports_color() {
+        return out.write_all(line);
+    }
+
+    let mut last = 0;
+    for span in matcher.find_all(line) {
+        out.write_all(&line[last..span.start])?;
+        last = span.end;
+        write_match(out, specs, matcher, replacement, line, span)?;
+    }
+
+    out.write_all(&line[last..])
+}
+
+/// Writes the match at `span` in `line` highlighted, or what it is replaced
+/// by when there is a replacement.
+fn write_match<W: WriteColor>(
+    out: &mut W,
+    specs: &ColorSpecs,
+    matcher: &dyn Matcher,
+    replacement: Option<&[u8]>,
+    line: &[u8],
+    span: Range<usize>,
+) -> io::Result<()> {
+    out.set_color(&specs.matched)?;
+    match replacement {
+        Some(replacement) => {
+            let mut replaced = Vec::new();
+            matcher.replace(line, span, replacement, &mut replaced);
+            out.write_all(&replaced)?;
+        }
+        None => out.write_all(&line[span])?,
+    }
+    out.reset()
+}
+
+/// Returns `bytes` as `{"text": ...}` if they are valid UTF-8, or else as
+/// `{"bytes": ...}` in base64.
+fn json_data(bytes: &[u8]) -> serde_json::Value {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => json!({ "text": text }),
+        Err(_) => json!({ "bytes": base64::engine::general_purpose::STANDARD.encode(bytes) }),
+    }
+}
+
+fn json_path(path: &Path) -> serde_json::Value {
+    if path == Path::new(args::STDIN) {
+        return json!({ "text": args::STDIN_LABEL });
+    }
+
+    json_data(path.as_os_str().as_encoded_bytes())
+}
+
+fn json_duration(duration: Duration) -> serde_json::Value {
+    json!({
+        "secs": duration.as_secs(),
+        "nanos": duration.subsec_nanos(),
+        "human": format!("{:.6}s", duration.as_secs_f64()),
+    })
+}
+
+fn json_stats(stats: &Stats) -> serde_json::Value {
+    json!({
+        "elapsed": json_duration(stats.elapsed),
+        "searches": stats.searches,
+        "searches_with_match": stats.searches_with_match,
+        "bytes_searched": stats.bytes_searched,
+        "matched_lines": stats.matched_lines,
+        "matches": stats.matches,
+    })
+}
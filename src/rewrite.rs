@@ -0,0 +1,533 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use termcolor::WriteColor;
+
+use crate::color::ColorSpecs;
+use crate::matcher::Matcher;
+use crate::printer::display_path;
+use crate::searcher::{Line, Sink, Stats};
+
+/// The number of unchanged lines shown around each change in a diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// A matching line and what it is rewritten to.
+#[derive(Debug)]
+struct Edit {
+    line_number: u64,
+    /// The line as it was searched, without its line terminator.
+    original: Vec<u8>,
+    /// The line with every match replaced, without its line terminator.
+    replaced: Vec<u8>,
+}
+
+/// Rewrites files with every match replaced.
+///
+/// The rewritten lines are collected while a file is searched, and the file
+/// is rewritten once the search is done. Only the path of a rewritten file is
+/// printed, or with a dry run, nothing is rewritten and a unified diff of
+/// the changes is printed instead.
+///
+/// Files containing binary data are never rewritten.
+#[derive(Debug)]
+pub struct Rewriter<'a, W> {
+    out: W,
+    specs: &'a ColorSpecs,
+    matcher: &'a dyn Matcher,
+    replacement: &'a [u8],
+    dry_run: bool,
+    /// Whether the original of a file is kept next to it, as `FILE.bak`.
+    backup: bool,
+    /// The path of the file being searched.
+    path: PathBuf,
+    edits: Vec<Edit>,
+    /// Set once binary data has been seen in the file being searched.
+    binary: bool,
+}
+
+impl<'a, W: WriteColor> Rewriter<'a, W> {
+    pub fn new(
+        out: W,
+        specs: &'a ColorSpecs,
+        matcher: &'a dyn Matcher,
+        replacement: &'a [u8],
+        dry_run: bool,
+        backup: bool,
+    ) -> Rewriter<'a, W> {
+        Rewriter {
+            out,
+            specs,
+            matcher,
+            replacement,
+            dry_run,
+            backup,
+            path: PathBuf::new(),
+            edits: Vec::new(),
+            binary: false,
+        }
+    }
+
+    /// Returns the contents of the file with the edits applied.
+    ///
+    /// This fails if the file turns out to be binary or has changed since it
+    /// was searched.
+    fn rewrite(&self, original: &[u8]) -> io::Result<Vec<u8>> {
+        if memchr::memchr(b'\0', original).is_some() {
+            return Err(binary_error());
+        }
+
+        let mut rewritten = Vec::with_capacity(original.len());
+        let mut edits = self.edits.iter().peekable();
+
+        for (i, line) in lines(original).enumerate() {
+            let (content, terminator) = split_terminator(line);
+
+            let Some(edit) = edits.next_if(|edit| edit.line_number == i as u64 + 1) else {
+                rewritten.extend_from_slice(line);
+                continue;
+            };
+
+            if edit.original != content {
+                return Err(io::Error::other("file changed while it was searched"));
+            }
+
+            rewritten.extend_from_slice(&edit.replaced);
+            rewritten.extend_from_slice(terminator);
+        }
+
+        Ok(rewritten)
+    }
+
+    /// Writes a unified diff of the edits to `out`.
+    fn write_diff(&mut self, original: &[u8]) -> io::Result<()> {
+        let lines = lines(original).collect::<Vec<_>>();
+        let path = display_path(&self.path).into_owned();
+
+        writeln!(self.out, "--- a/{path}")?;
+        writeln!(self.out, "+++ b/{path}")?;
+
+        // how far line numbers in the new file are ahead of the old ones,
+        // since a replacement may contain line terminators of its own
+        let mut shift = 0;
+        let mut edits = &self.edits[..];
+
+        while let Some(first) = edits.first() {
+            // a hunk takes in every edit whose context touches the previous
+            // one
+            let len = 1 + edits
+                .windows(2)
+                .take_while(|pair| {
+                    pair[1].line_number - pair[0].line_number <= 2 * DIFF_CONTEXT as u64 + 1
+                })
+                .count();
+            let (hunk, rest) = edits.split_at(len);
+            edits = rest;
+
+            let first = first.line_number as usize - 1;
+            let last = hunk[hunk.len() - 1].line_number as usize - 1;
+            let start = first.saturating_sub(DIFF_CONTEXT);
+            let end = std::cmp::min(lines.len(), last + 1 + DIFF_CONTEXT);
+
+            let added = hunk
+                .iter()
+                .map(|edit| memchr::memchr_iter(b'\n', &edit.replaced).count())
+                .sum::<usize>();
+            let old_len = end - start;
+            let new_len = old_len + added;
+
+            writeln!(
+                self.out,
+                "@@ -{},{} +{},{} @@",
+                start + 1,
+                old_len,
+                start + 1 + shift,
+                new_len
+            )?;
+            shift += added;
+
+            let mut hunk = hunk.iter().peekable();
+            for (i, line) in lines.iter().enumerate().take(end).skip(start) {
+                let (content, terminator) = split_terminator(line);
+
+                let Some(edit) = hunk.next_if(|edit| edit.line_number as usize == i + 1) else {
+                    write_diff_line(&mut self.out, b' ', content, terminator)?;
+                    continue;
+                };
+
+                write_diff_line(&mut self.out, b'-', content, terminator)?;
+
+                let parts = edit.replaced.split(|&b| b == b'\n').collect::<Vec<_>>();
+                for (j, part) in parts.iter().enumerate() {
+                    // the original terminator only follows the last part
+                    let terminator = match j + 1 == parts.len() {
+                        true => terminator,
+                        false => &b"\n"[..],
+                    };
+                    write_diff_line(&mut self.out, b'+', part, terminator)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: WriteColor> Sink for Rewriter<'_, W> {
+    fn begin(&mut self, path: &Path) -> io::Result<()> {
+        path.clone_into(&mut self.path);
+        self.edits.clear();
+        self.binary = false;
+        Ok(())
+    }
+
+    fn matched(&mut self, line_number: u64, line: &Line<'_>) -> io::Result<()> {
+        if line.binary_offset.is_some() {
+            self.binary = true;
+            return Ok(());
+        }
+
+        let mut replaced = Vec::with_capacity(line.bytes.len());
+        let mut last = 0;
+        for span in self.matcher.find_all(line.bytes) {
+            replaced.extend_from_slice(&line.bytes[last..span.start]);
+            last = span.end;
+            self.matcher
+                .replace(line.bytes, span, self.replacement, &mut replaced);
+        }
+        replaced.extend_from_slice(&line.bytes[last..]);
+
+        if replaced != line.bytes {
+            self.edits.push(Edit {
+                line_number,
+                original: line.bytes.to_vec(),
+                replaced,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn context(&mut self, _: u64, _: u64, _: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn end(&mut self, _: &Stats, binary_offset: Option<u64>) -> io::Result<()> {
+        if self.binary || binary_offset.is_some() {
+            return Err(binary_error());
+        }
+
+        if self.edits.is_empty() {
+            return Ok(());
+        }
+
+        let original = fs::read(&self.path)?;
+        let rewritten = self.rewrite(&original)?;
+
+        if self.dry_run {
+            return self.write_diff(&original);
+        }
+
+        write_atomically(&self.path, &rewritten, self.backup)?;
+
+        self.out.set_color(&self.specs.path)?;
+        write!(self.out, "{}", display_path(&self.path))?;
+        self.out.reset()?;
+        writeln!(self.out)
+    }
+}
+
+/// Writes a line of a diff, marking a last line without a terminator the way
+/// `diff` does.
+fn write_diff_line<W: Write>(
+    out: &mut W,
+    marker: u8,
+    content: &[u8],
+    terminator: &[u8],
+) -> io::Result<()> {
+    out.write_all(&[marker])?;
+    out.write_all(content)?;
+    writeln!(out)?;
+
+    if terminator.is_empty() {
+        writeln!(out, r"\ No newline at end of file")?;
+    }
+
+    Ok(())
+}
+
+fn binary_error() -> io::Error {
+    io::Error::other("binary file, not rewritten")
+}
+
+/// Returns the lines of `data`, each with its line terminator.
+fn lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    data.split_inclusive(|&b| b == b'\n')
+}
+
+/// Splits a line into its contents and its line terminator, either `\n`,
+/// `\r\n` or nothing for the last line, the same way the searcher does.
+fn split_terminator(line: &[u8]) -> (&[u8], &[u8]) {
+    let len = match line.strip_suffix(b"\n") {
+        Some(rest) => rest.strip_suffix(b"\r").unwrap_or(rest).len(),
+        None => line.len(),
+    };
+
+    line.split_at(len)
+}
+
+/// Replaces the contents of the file at `path` with `contents`, keeping its
+/// permissions.
+///
+/// The contents are written to a temporary file next to it, which is flushed
+/// to disk and then renamed over the original, so that the file is either
+/// rewritten completely or not at all. Symbolic links are followed, the file
+/// they point to is rewritten.
+fn write_atomically(path: &Path, contents: &[u8], backup: bool) -> io::Result<()> {
+    let path = fs::canonicalize(path)?;
+    let metadata = fs::metadata(&path)?;
+
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(io::Error::other("not a file"));
+    };
+    let tmp = dir.join(format!(
+        ".{}.minigrep-{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = (|| {
+        let mut file = fs::File::create_new(&tmp)?;
+        file.write_all(contents)?;
+        file.set_permissions(metadata.permissions())?;
+        file.sync_all()?;
+
+        if backup {
+            let mut backup = path.clone().into_os_string();
+            backup.push(".bak");
+            fs::copy(&path, backup)?;
+        }
+
+        fs::rename(&tmp, &path)
+    })();
+
+    if result.is_err() {
+        // nothing useful can be done if the cleanup fails as well
+        let _ = fs::remove_file(&tmp);
+        return result;
+    }
+
+    // make the rename itself durable
+    #[cfg(unix)]
+    fs::File::open(dir)?.sync_all()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use termcolor::NoColor;
+
+    use super::*;
+    use crate::args::{CaseMode, Searchmode};
+    use crate::matcher;
+    use crate::searcher::LineReader;
+
+    /// Searches `original` for the regex `pattern` and returns it rewritten
+    /// with `replacement`, along with the diff of the changes.
+    fn rewrite(pattern: &str, replacement: &str, original: &str) -> (String, String) {
+        let config = matcher::Config {
+            mode: Searchmode::Regex,
+            case: CaseMode::Sensitive,
+            word: false,
+            line: false,
+            replace: true,
+        };
+        let matcher = matcher::new(&config, &[pattern.to_string()]).unwrap();
+        let specs = ColorSpecs::new(&[]);
+
+        let mut rewriter = Rewriter::new(
+            NoColor::new(Vec::new()),
+            &specs,
+            matcher.as_ref(),
+            replacement.as_bytes(),
+            true,
+            false,
+        );
+        rewriter.begin(Path::new("file")).unwrap();
+
+        let mut lines = LineReader::new(original.as_bytes());
+        let mut line_number = 0;
+        while let Some(line) = lines.next_line().unwrap() {
+            line_number += 1;
+            if matcher.is_match(line.bytes) {
+                rewriter.matched(line_number, &line).unwrap();
+            }
+        }
+
+        let rewritten = rewriter.rewrite(original.as_bytes()).unwrap();
+        rewriter.write_diff(original.as_bytes()).unwrap();
+
+        (
+            String::from_utf8(rewritten).unwrap(),
+            String::from_utf8(rewriter.out.into_inner()).unwrap(),
+        )
+    }
+
+    /// Returns the lines `1` to `n`, each of them the word `line` followed by
+    /// its number.
+    fn numbered_lines(n: usize) -> String {
+        (1..=n).map(|i| format!("line{i}\n")).collect()
+    }
+
+    #[test]
+    fn rewrite_keeps_line_terminators() {
+        let (rewritten, _) = rewrite("foo", "baz", "foo\r\nbar\nfoo\n");
+        assert_eq!(rewritten, "baz\r\nbar\nbaz\n");
+    }
+
+    #[test]
+    fn rewrite_expands_capture_groups() {
+        let (rewritten, _) = rewrite(r"(\w+)=(\w+)", "$2=$1", "a=b\nc\n");
+        assert_eq!(rewritten, "b=a\nc\n");
+    }
+
+    #[test]
+    fn rewrite_fails_if_the_file_changed() {
+        let config = matcher::Config {
+            mode: Searchmode::Standard,
+            case: CaseMode::Sensitive,
+            word: false,
+            line: false,
+            replace: true,
+        };
+        let matcher = matcher::new(&config, &["foo".to_string()]).unwrap();
+        let specs = ColorSpecs::new(&[]);
+
+        let mut rewriter = Rewriter::new(
+            NoColor::new(Vec::new()),
+            &specs,
+            matcher.as_ref(),
+            b"bar",
+            false,
+            false,
+        );
+        let line = Line {
+            bytes: b"foo",
+            offset: 0,
+            binary_offset: None,
+        };
+        rewriter.matched(1, &line).unwrap();
+
+        assert!(rewriter.rewrite(b"foo\n").is_ok());
+        assert!(rewriter.rewrite(b"changed\n").is_err());
+        assert!(rewriter.rewrite(b"foo\0\n").is_err());
+    }
+
+    #[test]
+    fn diff_merges_hunks_whose_context_touches() {
+        let original = numbered_lines(12);
+        let (_, diff) = rewrite("^line(2|9)$", "changed$1", &original);
+
+        assert_eq!(
+            diff,
+            "--- a/file\n\
+             +++ b/file\n\
+             @@ -1,12 +1,12 @@\n \
+             line1\n\
+             -line2\n\
+             +changed2\n \
+             line3\n \
+             line4\n \
+             line5\n \
+             line6\n \
+             line7\n \
+             line8\n\
+             -line9\n\
+             +changed9\n \
+             line10\n \
+             line11\n \
+             line12\n"
+        );
+    }
+
+    #[test]
+    fn diff_splits_distant_changes_into_hunks() {
+        let original = numbered_lines(14);
+        let (_, diff) = rewrite("^line(2|10)$", "changed$1", &original);
+
+        assert_eq!(
+            diff,
+            "--- a/file\n\
+             +++ b/file\n\
+             @@ -1,5 +1,5 @@\n \
+             line1\n\
+             -line2\n\
+             +changed2\n \
+             line3\n \
+             line4\n \
+             line5\n\
+             @@ -7,7 +7,7 @@\n \
+             line7\n \
+             line8\n \
+             line9\n\
+             -line10\n\
+             +changed10\n \
+             line11\n \
+             line12\n \
+             line13\n"
+        );
+    }
+
+    #[test]
+    fn diff_counts_lines_added_by_replacements() {
+        let original = numbered_lines(14);
+        let (rewritten, diff) = rewrite("^line(2|12)$", "a\nb$1", &original);
+
+        assert!(rewritten.starts_with("line1\na\nb2\nline3\n"));
+        assert_eq!(
+            diff,
+            "--- a/file\n\
+             +++ b/file\n\
+             @@ -1,5 +1,6 @@\n \
+             line1\n\
+             -line2\n\
+             +a\n\
+             +b2\n \
+             line3\n \
+             line4\n \
+             line5\n\
+             @@ -9,6 +10,7 @@\n \
+             line9\n \
+             line10\n \
+             line11\n\
+             -line12\n\
+             +a\n\
+             +b12\n \
+             line13\n \
+             line14\n"
+        );
+    }
+
+    #[test]
+    fn diff_marks_a_missing_final_newline() {
+        let (rewritten, diff) = rewrite("bar", "baz", "foo\nbar");
+
+        assert_eq!(rewritten, "foo\nbaz");
+        assert_eq!(
+            diff,
+            "--- a/file\n\
+             +++ b/file\n\
+             @@ -1,2 +1,2 @@\n \
+             foo\n\
+             -bar\n\
+             \\ No newline at end of file\n\
+             +baz\n\
+             \\ No newline at end of file\n"
+        );
+    }
+}
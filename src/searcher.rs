@@ -0,0 +1,774 @@
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::args::{self, BinaryMode, Mode};
+use crate::matcher::Matcher;
+
+/// The options a file is searched with.
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub mode: Mode,
+    pub binary: BinaryMode,
+    /// Whether lines that don't match are the ones searched for.
+    pub invert: bool,
+    /// Whether only the matches are printed instead of the whole lines.
+    pub only_matching: bool,
+    /// Whether the byte offset in the input is printed with each line.
+    pub byte_offset: bool,
+    /// The number of lines to show before each match.
+    pub before_context: usize,
+    /// The number of lines to show after each match.
+    pub after_context: usize,
+    /// Whether every match in a matching line is counted for the statistics,
+    /// and not just the line.
+    pub stats: bool,
+    /// Whether the search stops at the first match, since nothing is printed
+    /// and a single match is all there is to know.
+    pub quiet: bool,
+}
+
+/// Statistics about one or more searches.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    /// The time spent searching.
+    pub elapsed: Duration,
+    /// The number of files searched.
+    pub searches: u64,
+    /// The number of files searched with at least one match.
+    pub searches_with_match: u64,
+    /// The number of bytes read from the files searched.
+    pub bytes_searched: u64,
+    /// The number of matching lines.
+    pub matched_lines: u64,
+    /// The number of matches, only counted when asked for, otherwise each
+    /// matching line counts as one.
+    pub matches: u64,
+}
+
+impl Stats {
+    /// Adds the statistics of `other` to these.
+    pub fn add(&mut self, other: &Stats) {
+        self.elapsed += other.elapsed;
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.bytes_searched += other.bytes_searched;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+    }
+}
+
+/// Receives the events of searching a single file.
+///
+/// For every file searched, `begin` is called first and `end` last, with
+/// matching lines, context lines and breaks between groups of context in
+/// between. This is how printers get what to print, each deciding by itself
+/// what, if anything, to write for each event.
+pub trait Sink {
+    /// Called when the search of `path` starts.
+    fn begin(&mut self, path: &Path) -> io::Result<()>;
+
+    /// Called for a matching line, or with inverted matching, a line that
+    /// doesn't match.
+    fn matched(&mut self, line_number: u64, line: &Line<'_>) -> io::Result<()>;
+
+    /// Called for a line shown as context around a match.
+    fn context(&mut self, line_number: u64, offset: u64, line: &[u8]) -> io::Result<()>;
+
+    /// Called between two groups of lines that aren't contiguous.
+    fn context_break(&mut self) -> io::Result<()>;
+
+    /// Called when the search of the file is done.
+    ///
+    /// When the search stopped at a match in binary data, `binary_offset` is
+    /// the offset of the first NUL byte found.
+    fn end(&mut self, stats: &Stats, binary_offset: Option<u64>) -> io::Result<()>;
+}
+
+/// Searches files line by line, reporting what it finds to a `Sink`.
+#[derive(Debug)]
+pub struct Searcher<'a> {
+    config: &'a Config,
+    matcher: &'a dyn Matcher,
+}
+
+impl<'a> Searcher<'a> {
+    pub fn new(config: &'a Config, matcher: &'a dyn Matcher) -> Searcher<'a> {
+        Searcher { config, matcher }
+    }
+
+    /// Searches a single file, or standard input for `-`, reporting the
+    /// matching lines and the context around them to `sink`.
+    ///
+    /// `explicit` tells whether the user asked for this file, rather than it
+    /// being found in a directory, which decides what happens when it turns
+    /// out to contain binary data.
+    ///
+    /// Returns the statistics of the search, or `None` if the file was
+    /// skipped.
+    pub fn search_path(
+        &self,
+        path: &Path,
+        explicit: bool,
+        sink: &mut dyn Sink,
+    ) -> io::Result<Option<Stats>> {
+        let rdr: Box<dyn Read> = if path == Path::new(args::STDIN) {
+            Box::new(io::stdin().lock())
+        } else {
+            Box::new(std::fs::File::open(path)?)
+        };
+
+        sink.begin(path)?;
+        self.search_reader(rdr, explicit, sink)
+    }
+
+    fn search_reader<R: Read>(
+        &self,
+        rdr: R,
+        explicit: bool,
+        sink: &mut dyn Sink,
+    ) -> io::Result<Option<Stats>> {
+        let config = self.config;
+        let start = Instant::now();
+
+        let mut lines = LineReader::new(rdr);
+        let mut line_number = 0;
+
+        // the lines before the current one, for before context
+        let mut before = VecDeque::with_capacity(config.before_context);
+        // the number of lines still to be shown as after context
+        let mut after = 0;
+        // the number of the last line reported, to know when a break is needed
+        let mut last_reported = None;
+        // the offset of the NUL byte of a binary file that matched
+        let mut binary_match = None;
+
+        let mut stats = Stats {
+            searches: 1,
+            ..Default::default()
+        };
+
+        while let Some(line) = lines.next_line()? {
+            line_number += 1;
+
+            let binary_offset = match config.binary {
+                BinaryMode::AsText => None,
+                BinaryMode::Auto | BinaryMode::SearchAndSuppress => line.binary_offset,
+            };
+
+            // binary files found while walking a directory are skipped,
+            // unless asked otherwise. Whether a file is binary is decided by
+            // the first buffer read, before anything has been reported, since
+            // what went to the sink may already be printed. Binary data found
+            // further on is handled the same way as in files given
+            // explicitly.
+            if line_number == 1
+                && binary_offset.is_some()
+                && !explicit
+                && matches!(config.binary, BinaryMode::Auto)
+            {
+                return Ok(None);
+            }
+
+            // with inverted matching, the lines that don't match are reported
+            if self.is_match(line.bytes) == config.invert {
+                if binary_offset.is_some() {
+                    continue;
+                }
+
+                if after > 0 {
+                    after -= 1;
+                    self.context_break(sink, last_reported, line_number)?;
+                    sink.context(line_number, line.offset, line.bytes)?;
+                    last_reported = Some(line_number);
+                } else if config.before_context > 0 {
+                    // reuse the allocation of the line falling out of the buffer
+                    let mut bytes = match before.len() == config.before_context {
+                        true => before
+                            .pop_front()
+                            .map(|(_, _, bytes)| bytes)
+                            .unwrap_or_default(),
+                        false => Vec::new(),
+                    };
+                    bytes.clear();
+                    bytes.extend_from_slice(line.bytes);
+                    before.push_back((line_number, line.offset, bytes));
+                }
+                continue;
+            }
+
+            stats.matched_lines += 1;
+            stats.matches += match config.mode {
+                // an inverted match is a whole line, not a span in it
+                _ if config.invert => 1,
+                Mode::CountMatches(_) => self.count_matches(line.bytes),
+                _ if config.stats => self.count_matches(line.bytes),
+                _ => 1,
+            };
+
+            // listing modes know the answer at the first match, and so does
+            // quiet mode
+            if config.quiet
+                || matches!(
+                    config.mode,
+                    Mode::FilesWithMatches(_) | Mode::FilesWithoutMatch(_)
+                )
+            {
+                break;
+            }
+
+            // counting modes report nothing but the total at the end
+            if let Mode::Count(_) | Mode::CountMatches(_) = config.mode {
+                continue;
+            }
+
+            // binary data is never reported, a single match is enough to say
+            // that the file matches
+            if binary_offset.is_some() {
+                binary_match = binary_offset;
+                break;
+            }
+
+            for (number, offset, bytes) in before.drain(..) {
+                self.context_break(sink, last_reported, number)?;
+                sink.context(number, offset, &bytes)?;
+                last_reported = Some(number);
+            }
+
+            self.context_break(sink, last_reported, line_number)?;
+            sink.matched(line_number, &line)?;
+            last_reported = Some(line_number);
+            after = config.after_context;
+        }
+
+        stats.searches_with_match = u64::from(stats.matched_lines > 0);
+        stats.bytes_searched = lines.bytes_read();
+        stats.elapsed = start.elapsed();
+
+        sink.end(&stats, binary_match)?;
+        Ok(Some(stats))
+    }
+
+    /// Returns true if `line` matches.
+    ///
+    /// Only the matches themselves are printed with only-matching, so a line
+    /// whose matches are all empty has nothing to show and doesn't count.
+    fn is_match(&self, line: &[u8]) -> bool {
+        match self.config.only_matching && !self.config.invert {
+            true => !self.matcher.find_all(line).is_empty(),
+            false => self.matcher.is_match(line),
+        }
+    }
+
+    /// Returns the number of matches in a matching line.
+    fn count_matches(&self, line: &[u8]) -> u64 {
+        // empty matches are skipped by `find_all`, but the line still matched
+        // at least once
+        std::cmp::max(1, self.matcher.find_all(line).len() as u64)
+    }
+
+    /// Reports a break when `line_number` doesn't directly follow the last
+    /// line reported, i.e., between groups of lines that aren't contiguous.
+    ///
+    /// Breaks are only reported when context is shown.
+    fn context_break(
+        &self,
+        sink: &mut dyn Sink,
+        last_reported: Option<u64>,
+        line_number: u64,
+    ) -> io::Result<()> {
+        if self.config.before_context == 0 && self.config.after_context == 0 {
+            return Ok(());
+        }
+
+        match last_reported {
+            Some(last) if line_number > last + 1 => sink.context_break(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The number of bytes read from the underlying reader at a time.
+const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+/// A line returned by `LineReader`.
+#[derive(Debug)]
+pub struct Line<'a> {
+    /// The contents of the line without its line terminator, either `\n` or
+    /// `\r\n`.
+    pub bytes: &'a [u8],
+    /// The absolute offset of the start of the line in the input.
+    pub offset: u64,
+    /// The absolute offset of the first NUL byte read so far, if any.
+    ///
+    /// Since input is read ahead a buffer at a time, this may point at a NUL
+    /// byte that comes after this line.
+    pub binary_offset: Option<u64>,
+}
+
+/// Reads lines from a reader through a fixed-size buffer.
+///
+/// Only the unconsumed part of the input is kept in memory, so the memory
+/// used is bounded by the buffer size (or the longest line, if that is
+/// larger) no matter how large the input is. Lines that span a buffer
+/// boundary are handled by moving the partial line to the front of the buffer
+/// before reading more.
+///
+/// While reading, the input is checked for NUL bytes, which is how binary
+/// data is detected.
+#[derive(Debug)]
+pub struct LineReader<R> {
+    rdr: R,
+    buf: Vec<u8>,
+    /// The start of the data in `buf` that has not been returned yet.
+    start: usize,
+    /// The end of the valid data in `buf`.
+    end: usize,
+    /// Set once the reader has reported the end of input.
+    eof: bool,
+    /// The absolute offset in the input of the first byte in `buf`.
+    offset: u64,
+    /// The absolute offset of the first NUL byte read, if any.
+    binary_offset: Option<u64>,
+}
+
+impl<R: Read> LineReader<R> {
+    pub fn new(rdr: R) -> LineReader<R> {
+        LineReader {
+            rdr,
+            buf: vec![0; DEFAULT_CAPACITY],
+            start: 0,
+            end: 0,
+            eof: false,
+            offset: 0,
+            binary_offset: None,
+        }
+    }
+
+    /// Returns the next line, or `None` at the end of input.
+    pub fn next_line(&mut self) -> io::Result<Option<Line<'_>>> {
+        let Some(range) = self.next_line_range()? else {
+            return Ok(None);
+        };
+
+        let offset = self.offset + range.start as u64;
+
+        let mut bytes = &self.buf[range];
+        if let Some(rest) = bytes.strip_suffix(b"\n") {
+            bytes = rest.strip_suffix(b"\r").unwrap_or(rest);
+        }
+
+        Ok(Some(Line {
+            bytes,
+            offset,
+            binary_offset: self.binary_offset,
+        }))
+    }
+
+    /// Returns the number of bytes read from the underlying reader so far.
+    ///
+    /// Since input is read ahead a buffer at a time, this may be more than
+    /// the lines returned so far add up to.
+    pub fn bytes_read(&self) -> u64 {
+        self.offset + self.end as u64
+    }
+
+    /// Returns the range in `buf` of the next line, including its line
+    /// terminator, filling the buffer as needed.
+    fn next_line_range(&mut self) -> io::Result<Option<Range<usize>>> {
+        loop {
+            let pending = &self.buf[self.start..self.end];
+
+            if let Some(i) = memchr::memchr(b'\n', pending) {
+                let range = self.start..self.start + i + 1;
+                self.start = range.end;
+                return Ok(Some(range));
+            }
+
+            if self.eof {
+                // the last line has no line terminator
+                if self.start == self.end {
+                    return Ok(None);
+                }
+
+                let range = self.start..self.end;
+                self.start = self.end;
+                return Ok(Some(range));
+            }
+
+            self.fill()?;
+        }
+    }
+
+    /// Reads more data into the buffer, keeping the partial line at its
+    /// front and growing it when the partial line fills it completely.
+    fn fill(&mut self) -> io::Result<()> {
+        self.buf.copy_within(self.start..self.end, 0);
+        self.offset += self.start as u64;
+        self.end -= self.start;
+        self.start = 0;
+
+        if self.end == self.buf.len() {
+            self.buf.resize(self.buf.len() * 2, 0);
+        }
+
+        let n = loop {
+            match self.rdr.read(&mut self.buf[self.end..]) {
+                Ok(n) => break n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        };
+
+        if self.binary_offset.is_none() {
+            let read = &self.buf[self.end..self.end + n];
+            if let Some(i) = memchr::memchr(b'\0', read) {
+                self.binary_offset = Some(self.offset + (self.end + i) as u64);
+            }
+        }
+
+        match n {
+            0 => self.eof = true,
+            n => self.end += n,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{CaseMode, Searchmode};
+    use crate::matcher;
+
+    /// A sink that records every event as a line of text.
+    #[derive(Debug, Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl Sink for Recorder {
+        fn begin(&mut self, _: &Path) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn matched(&mut self, line_number: u64, line: &Line<'_>) -> io::Result<()> {
+            let line = String::from_utf8_lossy(line.bytes);
+            self.events.push(format!("{line_number}:{line}"));
+            Ok(())
+        }
+
+        fn context(&mut self, line_number: u64, _: u64, line: &[u8]) -> io::Result<()> {
+            let line = String::from_utf8_lossy(line);
+            self.events.push(format!("{line_number}-{line}"));
+            Ok(())
+        }
+
+        fn context_break(&mut self) -> io::Result<()> {
+            self.events.push("--".to_string());
+            Ok(())
+        }
+
+        fn end(&mut self, stats: &Stats, binary_offset: Option<u64>) -> io::Result<()> {
+            self.events.push(format!(
+                "end {} {} {:?}",
+                stats.matched_lines, stats.matches, binary_offset
+            ));
+            Ok(())
+        }
+    }
+
+    /// Returns the options to search for literals with, in search mode.
+    fn config() -> Config {
+        Config {
+            mode: Mode::Search(Searchmode::Standard),
+            binary: BinaryMode::Auto,
+            invert: false,
+            only_matching: false,
+            byte_offset: false,
+            before_context: 0,
+            after_context: 0,
+            stats: false,
+            quiet: false,
+        }
+    }
+
+    /// Searches `input` for `pattern`, returning the events recorded, or
+    /// `None` if the input was skipped.
+    fn search(config: &Config, pattern: &str, input: &[u8], explicit: bool) -> Option<Vec<String>> {
+        let matcher_config = matcher::Config {
+            mode: Searchmode::Standard,
+            case: CaseMode::Sensitive,
+            word: false,
+            line: false,
+            replace: false,
+        };
+        let matcher = matcher::new(&matcher_config, &[pattern.to_string()]).unwrap();
+
+        let mut recorder = Recorder::default();
+        Searcher::new(config, matcher.as_ref())
+            .search_reader(input, explicit, &mut recorder)
+            .unwrap()
+            .map(|_| recorder.events)
+    }
+
+    #[test]
+    fn binary_files_found_while_walking_are_skipped() {
+        assert_eq!(search(&config(), "foo", b"foo\n\0\n", false), None);
+        assert_eq!(
+            search(&config(), "foo", b"foo\n\0foo\n", true),
+            Some(vec!["end 1 1 Some(4)".to_string()])
+        );
+    }
+
+    #[test]
+    fn binary_data_after_the_first_buffer_is_not_skipped() {
+        // lines are reported before the binary data is found, and can't be
+        // taken back
+        let mut input = b"foo\n".to_vec();
+        input.extend(vec![b'a'; DEFAULT_CAPACITY]);
+        input.extend_from_slice(b"\n\0foo\n");
+
+        let binary_offset = DEFAULT_CAPACITY as u64 + 5;
+        assert_eq!(
+            search(&config(), "foo", &input, false),
+            Some(vec![
+                "1:foo".to_string(),
+                format!("end 2 2 Some({binary_offset})"),
+            ])
+        );
+    }
+
+    /// Returns the events recorded for `input`, searched as a file given
+    /// explicitly.
+    fn events(config: &Config, pattern: &str, input: &str) -> Vec<String> {
+        search(config, pattern, input.as_bytes(), true).unwrap()
+    }
+
+    #[test]
+    fn before_context_keeps_the_last_lines() {
+        let config = Config {
+            before_context: 2,
+            ..config()
+        };
+
+        assert_eq!(
+            events(&config, "foo", "a\nb\nc\nfoo\nfoo\nd\n"),
+            ["2-b", "3-c", "4:foo", "5:foo", "end 2 2 None"]
+        );
+    }
+
+    #[test]
+    fn breaks_between_groups_of_lines() {
+        let context = Config {
+            before_context: 1,
+            after_context: 1,
+            ..config()
+        };
+
+        assert_eq!(
+            events(&context, "foo", "foo\na\nfoo\nb\nc\nd\nfoo\ne"),
+            [
+                "1:foo",
+                "2-a",
+                "3:foo",
+                "4-b",
+                "--",
+                "6-d",
+                "7:foo",
+                "8-e",
+                "end 3 3 None"
+            ]
+        );
+
+        // without context, lines are never apart
+        assert_eq!(
+            events(&config(), "foo", "foo\na\nfoo\n"),
+            ["1:foo", "3:foo", "end 2 2 None"]
+        );
+    }
+
+    #[test]
+    fn after_context_wins_over_context() {
+        for rawargs in [["-C", "2", "-A", "0"], ["-A", "0", "-C", "2"]] {
+            let mut args = args::Args::default();
+            crate::parse::Parser::new()
+                .parse(rawargs, &mut args)
+                .unwrap();
+
+            let config = Config {
+                before_context: args.get_before_context(),
+                after_context: args.get_after_context(),
+                ..config()
+            };
+
+            assert_eq!(
+                events(&config, "foo", "a\nb\nc\nfoo\nd\ne\n"),
+                ["2-b", "3-c", "4:foo", "end 1 1 None"]
+            );
+        }
+    }
+
+    #[test]
+    fn inverted_matching_reports_the_other_lines() {
+        let config = Config {
+            invert: true,
+            ..config()
+        };
+        assert_eq!(
+            events(&config, "foo", "foo\nbar\nfoo\nbaz\n"),
+            ["2:bar", "4:baz", "end 2 2 None"]
+        );
+
+        // the matching lines are the context
+        let config = Config {
+            after_context: 1,
+            ..config
+        };
+        assert_eq!(
+            events(&config, "foo", "bar\nfoo\nfoo\n"),
+            ["1:bar", "2-foo", "end 1 1 None"]
+        );
+    }
+
+    #[test]
+    fn counting_reports_nothing_but_the_totals() {
+        let input = "foo foo\nbar\nfoo\n";
+
+        let count = Config {
+            mode: Mode::Count(Searchmode::Standard),
+            ..config()
+        };
+        assert_eq!(events(&count, "foo", input), ["end 2 2 None"]);
+
+        let count_matches = Config {
+            mode: Mode::CountMatches(Searchmode::Standard),
+            ..config()
+        };
+        assert_eq!(events(&count_matches, "foo", input), ["end 2 3 None"]);
+
+        // an inverted match counts once, whatever it contains
+        let inverted = Config {
+            invert: true,
+            ..count_matches
+        };
+        assert_eq!(events(&inverted, "foo", input), ["end 1 1 None"]);
+    }
+
+    #[test]
+    fn only_empty_matches_have_nothing_to_show() {
+        let only_matching = Config {
+            only_matching: true,
+            ..config()
+        };
+
+        assert_eq!(events(&only_matching, "", "abc\n"), ["end 0 0 None"]);
+        assert_eq!(events(&config(), "", "abc\n"), ["1:abc", "end 1 1 None"]);
+    }
+
+    /// Returns every line of `input`, with its offset, read through a
+    /// `LineReader`.
+    fn read_lines(input: &[u8]) -> Vec<(Vec<u8>, u64)> {
+        let mut lines = LineReader::new(input);
+        let mut read = Vec::new();
+
+        while let Some(line) = lines.next_line().unwrap() {
+            read.push((line.bytes.to_vec(), line.offset));
+        }
+
+        assert_eq!(lines.bytes_read(), input.len() as u64);
+        read
+    }
+
+    #[test]
+    fn lines_without_a_final_terminator() {
+        assert_eq!(
+            read_lines(b"foo\nbar\n\nbaz"),
+            vec![
+                (b"foo".to_vec(), 0),
+                (b"bar".to_vec(), 4),
+                (b"".to_vec(), 8),
+                (b"baz".to_vec(), 9),
+            ]
+        );
+        assert_eq!(read_lines(b""), vec![]);
+    }
+
+    #[test]
+    fn lines_crossing_the_buffer_boundary() {
+        let first = vec![b'a'; DEFAULT_CAPACITY - 3];
+        let mut input = first.clone();
+        input.extend_from_slice(b"\nbcdef\ng\n");
+
+        assert_eq!(
+            read_lines(&input),
+            vec![
+                (first, 0),
+                (b"bcdef".to_vec(), DEFAULT_CAPACITY as u64 - 2),
+                (b"g".to_vec(), DEFAULT_CAPACITY as u64 + 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_longer_than_the_buffer() {
+        let long = vec![b'x'; 3 * DEFAULT_CAPACITY + 5];
+        let mut input = b"short\n".to_vec();
+        input.extend_from_slice(&long);
+        input.extend_from_slice(b"\nend");
+
+        assert_eq!(
+            read_lines(&input),
+            vec![
+                (b"short".to_vec(), 0),
+                (long, 6),
+                (b"end".to_vec(), 3 * DEFAULT_CAPACITY as u64 + 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn crlf_terminators() {
+        assert_eq!(
+            read_lines(b"a\r\nb\nc\r\n\r\nd\r"),
+            vec![
+                (b"a".to_vec(), 0),
+                (b"b".to_vec(), 3),
+                (b"c".to_vec(), 5),
+                (b"".to_vec(), 8),
+                // only a `\r` followed by `\n` is part of a line terminator
+                (b"d\r".to_vec(), 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn crlf_split_by_the_buffer_boundary() {
+        let first = vec![b'a'; DEFAULT_CAPACITY - 1];
+        let mut input = first.clone();
+        input.extend_from_slice(b"\r\nb\r\n");
+
+        assert_eq!(
+            read_lines(&input),
+            vec![(first, 0), (b"b".to_vec(), DEFAULT_CAPACITY as u64 + 1)]
+        );
+    }
+
+    #[test]
+    fn binary_offset_of_the_first_nul_byte() {
+        let mut input = vec![b'a'; DEFAULT_CAPACITY + 10];
+        input[DEFAULT_CAPACITY + 2] = b'\0';
+        input[DEFAULT_CAPACITY + 5] = b'\0';
+        input.extend_from_slice(b"\nb\n");
+
+        let mut lines = LineReader::new(&input[..]);
+        let line = lines.next_line().unwrap().unwrap();
+        assert_eq!(line.binary_offset, Some(DEFAULT_CAPACITY as u64 + 2));
+        let line = lines.next_line().unwrap().unwrap();
+        assert_eq!(line.bytes, b"b");
+        assert_eq!(line.binary_offset, Some(DEFAULT_CAPACITY as u64 + 2));
+    }
+}
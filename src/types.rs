@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context as _;
+
+/// The built-in file types, each with the globs matching its files.
+const DEFAULT_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.[chH]", "*.[chH].in"]),
+    (
+        "cpp",
+        &[
+            "*.[ChH]", "*.cc", "*.cpp", "*.cxx", "*.hh", "*.hpp", "*.hxx", "*.inl",
+        ],
+    ),
+    ("css", &["*.css", "*.scss"]),
+    ("go", &["*.go"]),
+    ("html", &["*.htm", "*.html"]),
+    ("java", &["*.java"]),
+    ("js", &["*.cjs", "*.js", "*.jsx", "*.mjs"]),
+    ("json", &["*.json"]),
+    ("lock", &["*.lock", "package-lock.json"]),
+    (
+        "make",
+        &["*.mak", "*.mk", "GNUmakefile", "Makefile", "makefile"],
+    ),
+    ("md", &["*.markdown", "*.md", "*.mdown", "*.mkdn"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.bash", "*.sh", "*.zsh"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.cts", "*.mts", "*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// A file type given with -t/--type or -T/--type-not.
+#[derive(Debug, Clone)]
+pub enum Selection {
+    /// Only search files of this type. This correspondes to the '-t' flag
+    Select(String),
+    /// Don't search files of this type. This correspondes to the '-T' flag
+    Negate(String),
+}
+
+/// The table of file types, i.e., the built-in ones and those added with
+/// --type-add, by name.
+#[derive(Debug, Clone)]
+pub struct Types {
+    types: BTreeMap<String, Vec<String>>,
+}
+
+impl Types {
+    /// Returns the built-in types with `type_defs` added to them.
+    ///
+    /// A definition is of the form `name:glob`. The glob is added to the
+    /// globs of the type if it already exists, built-in or not.
+    pub fn new(type_defs: &[String]) -> anyhow::Result<Types> {
+        let mut types = DEFAULT_TYPES
+            .iter()
+            .map(|&(name, globs)| {
+                let globs = globs.iter().map(|glob| glob.to_string()).collect();
+                (name.to_string(), globs)
+            })
+            .collect::<BTreeMap<_, Vec<_>>>();
+
+        for type_def in type_defs {
+            let Some((name, glob)) = type_def.split_once(':') else {
+                anyhow::bail!("invalid type definition '{type_def}', expected {{name}}:{{glob}}");
+            };
+
+            anyhow::ensure!(
+                !name.is_empty() && name.chars().all(|ch| ch.is_alphanumeric() || ch == '_'),
+                "invalid type name '{name}' in type definition '{type_def}', expected only \
+                 letters, digits or '_'"
+            );
+            anyhow::ensure!(
+                !glob.is_empty(),
+                "missing glob in type definition '{type_def}'"
+            );
+
+            types
+                .entry(name.to_string())
+                .or_default()
+                .push(glob.to_string());
+        }
+
+        Ok(Types { types })
+    }
+
+    /// Returns the matcher that selects files according to `selections`,
+    /// see `walk::Config::types`.
+    pub fn matcher(&self, selections: &[Selection]) -> anyhow::Result<ignore::types::Types> {
+        let mut builder = ignore::types::TypesBuilder::new();
+
+        for (name, globs) in &self.types {
+            for glob in globs {
+                builder
+                    .add(name, glob)
+                    .with_context(|| format!("invalid glob '{glob}' for file type '{name}'"))?;
+            }
+        }
+
+        for selection in selections {
+            let (Selection::Select(name) | Selection::Negate(name)) = selection;
+            anyhow::ensure!(
+                self.types.contains_key(name),
+                "unrecognized file type '{name}', see --type-list for all types"
+            );
+
+            match selection {
+                Selection::Select(name) => builder.select(name),
+                Selection::Negate(name) => builder.negate(name),
+            };
+        }
+
+        Ok(builder.build()?)
+    }
+
+    /// Returns the table of types, one line per type with its name followed
+    /// by its globs.
+    pub fn list(&self) -> String {
+        self.types
+            .iter()
+            .map(|(name, globs)| format!("{}: {}", name, globs.join(", ")))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,773 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use anyhow::Context;
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+
+use crate::args;
+use crate::messages::err_message;
+
+/// Returns the number of worker threads to use when none was requested.
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(12)
+}
+
+/// The options a walk is done with.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The number of worker threads.
+    pub threads: usize,
+    /// Whether hidden files and directories, i.e., those whose name starts
+    /// with a `.`, are walked.
+    pub hidden: bool,
+    /// Whether the rules of ignore files are honoured.
+    pub ignore: bool,
+    /// The globs files must match, see `build_globs`.
+    pub globs: Override,
+    /// The file types files must be of, see `types::Types::matcher`.
+    pub types: ignore::types::Types,
+}
+
+/// Builds the matcher for the globs files must match to be searched.
+///
+/// Globs have the same syntax as the lines of a `.gitignore` file, except
+/// that a leading `!` excludes the files it matches instead of including
+/// them. When there is at least one glob without `!`, only files matching
+/// one of them are searched. Globs given later win over earlier ones.
+pub fn build_globs(globs: &[args::Glob]) -> anyhow::Result<Override> {
+    // globs with a `/` in them are relative to the current directory
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let mut builder = OverrideBuilder::new(cwd);
+
+    for glob in globs {
+        builder
+            .case_insensitive(glob.case_insensitive)?
+            .add(&glob.glob)
+            .with_context(|| format!("invalid glob '{}'", glob.glob))?;
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Recursively walks every root and calls `visit` on each regular file found.
+///
+/// Roots that are files or standard input are visited as is, roots that are
+/// directories are descended into. Symbolic links found while descending are
+/// not followed. The walk is spread over worker threads, so `visit` may be
+/// called concurrently from several threads.
+///
+/// What a visit returns besides what to do next, e.g., the output of a file,
+/// is handed to `emit`, one at a time and in the order of the roots: the
+/// results of a root are held back until every root before it is done. The
+/// results found in the same directory root come in no particular order.
+/// With a single worker thread, files are visited in that order to begin
+/// with, every root being walked to the end before the next one.
+///
+/// While descending, hidden entries are skipped unless asked otherwise, and
+/// so are the entries matched by the `.ignore`, `.gitignore` and
+/// `.git/info/exclude` files of the directories walked and of those above a
+/// root up to the root of its git repository, or by git's global ignore
+/// file. Globs given by the user win over all of these, and are the only
+/// thing applied to roots, and only to those that are files. File types are
+/// applied last, to the entries that are left.
+///
+/// Errors reading a directory or an entry are reported on stderr and the walk
+/// carries on with the rest of the tree.
+pub fn walk<T, V, E>(roots: Vec<PathBuf>, config: &Config, visit: V, emit: E)
+where
+    T: Send,
+    V: Fn(&Path, bool) -> (WalkState, Option<T>) + Sync,
+    E: FnMut(T) -> WalkState + Send,
+{
+    let queue = Queue::new(roots, config, emit);
+    let threads = std::cmp::max(1, config.threads);
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| queue.run(&visit));
+        }
+    });
+}
+
+/// What the walk should do after a file has been visited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkState {
+    /// Carry on walking.
+    Continue,
+    /// Stop walking as soon as possible.
+    Quit,
+}
+
+/// A unit of work in the queue, i.e., a path that has not been looked at yet.
+#[derive(Debug)]
+struct Work {
+    path: PathBuf,
+    /// Roots are given by the user, everything else is found while walking.
+    root: bool,
+    /// The index of the root the path was found in.
+    seq: usize,
+    /// The ignore rules of the directory the path is in.
+    ignore: Arc<Ignore>,
+}
+
+/// The ignore rules in effect in a directory, i.e., those of its own ignore
+/// files followed by those of every directory above it, up to the root of
+/// the git repository it is in, and finally git's global ignore file.
+///
+/// Rules found deeper in the tree win over the ones above them, the same way
+/// git handles nested `.gitignore` files.
+#[derive(Debug)]
+struct Ignore {
+    parent: Option<Arc<Ignore>>,
+    /// The matchers of the directory's own ignore files, by precedence.
+    matchers: Vec<Gitignore>,
+    /// For a directory above the root of the walk, the root as given and its
+    /// absolute path. The rules of such a directory are anchored at its
+    /// absolute path, which the paths found in the root are matched as.
+    root: Option<(PathBuf, PathBuf)>,
+}
+
+impl Ignore {
+    /// Returns the rules every walk starts with, i.e., the global ignore
+    /// file if there is one and ignore files are honoured at all.
+    fn global(config: &Config) -> Arc<Ignore> {
+        let mut matchers = Vec::new();
+
+        if config.ignore {
+            let (global, err) = Gitignore::global();
+            if let Some(err) = err {
+                err_message!("{}", err);
+            }
+            matchers.push(global);
+        }
+
+        Arc::new(Ignore {
+            parent: None,
+            matchers,
+            root: None,
+        })
+    }
+
+    /// Returns the rules in effect in the directory above `root`, a
+    /// directory given by the user, i.e., those of every directory from the
+    /// root of the git repository `root` is in down to its parent.
+    ///
+    /// Outside of a git repository, there are no such rules.
+    fn above(self: &Arc<Ignore>, config: &Config, root: &Path) -> Arc<Ignore> {
+        if !config.ignore {
+            return Arc::clone(self);
+        }
+
+        let Ok(absolute) = std::fs::canonicalize(root) else {
+            return Arc::clone(self);
+        };
+
+        // the root may well be the root of the repository itself
+        let mut dirs = Vec::new();
+        if !absolute.join(".git").exists() {
+            for dir in absolute.ancestors().skip(1) {
+                dirs.push(dir);
+                if dir.join(".git").exists() {
+                    break;
+                }
+            }
+            if !dirs.last().is_some_and(|dir| dir.join(".git").exists()) {
+                return Arc::clone(self);
+            }
+        }
+
+        let mut ignore = Arc::clone(self);
+        for dir in dirs.into_iter().rev() {
+            let matchers = read_ignore_files(dir);
+            if matchers.is_empty() {
+                continue;
+            }
+
+            ignore = Arc::new(Ignore {
+                parent: Some(ignore),
+                matchers,
+                root: Some((root.to_path_buf(), absolute.clone())),
+            });
+        }
+
+        ignore
+    }
+
+    /// Returns the rules in effect in `dir`, a directory in the directory
+    /// these rules are for.
+    fn child(self: &Arc<Ignore>, config: &Config, dir: &Path) -> Arc<Ignore> {
+        if !config.ignore {
+            return Arc::clone(self);
+        }
+
+        let matchers = read_ignore_files(dir);
+
+        // most directories have no ignore files, there is no need to make
+        // the chain of rules any longer for them
+        if matchers.is_empty() {
+            return Arc::clone(self);
+        }
+
+        Arc::new(Ignore {
+            parent: Some(Arc::clone(self)),
+            matchers,
+            root: None,
+        })
+    }
+
+    /// Returns true if `path` is matched by an ignore rule, and not
+    /// whitelisted by a rule with a higher precedence.
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut ignore = Some(self);
+
+        while let Some(current) = ignore {
+            let path = match &current.root {
+                Some((root, absolute)) => {
+                    Cow::Owned(absolute.join(path.strip_prefix(root).unwrap_or(path)))
+                }
+                None => Cow::Borrowed(path),
+            };
+
+            for matcher in &current.matchers {
+                match matcher.matched(&path, is_dir) {
+                    Match::None => {}
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                }
+            }
+            ignore = current.parent.as_deref();
+        }
+
+        false
+    }
+}
+
+/// Returns the matchers of the ignore files in `dir`, by precedence.
+fn read_ignore_files(dir: &Path) -> Vec<Gitignore> {
+    // `.ignore` files are for tools like this one and win over git's own,
+    // and the repository wide excludes have the lowest precedence
+    let files = [
+        dir.join(".ignore"),
+        dir.join(".gitignore"),
+        dir.join(".git").join("info").join("exclude"),
+    ];
+
+    let mut matchers = Vec::new();
+    for file in files.iter().filter(|file| file.is_file()) {
+        // the rules of every file are relative to `dir`, including those in
+        // `.git/info/exclude`
+        let mut builder = GitignoreBuilder::new(dir);
+        if let Some(err) = builder.add(file) {
+            err_message!("{}", err);
+        }
+
+        match builder.build() {
+            Ok(matcher) if !matcher.is_empty() => matchers.push(matcher),
+            Ok(_) => {}
+            Err(err) => {
+                err_message!("{}", err);
+            }
+        }
+    }
+
+    matchers
+}
+
+struct State<T> {
+    items: VecDeque<Work>,
+    /// The number of workers currently processing an item. The walk is done
+    /// once there are no items left and no worker can produce new ones.
+    active: usize,
+    /// Set once a visit asked to stop the walk.
+    quit: bool,
+    /// The number of items of each root that are queued or being processed.
+    /// A root is done once it has none left.
+    pending: Vec<usize>,
+    /// The first root that is not done, whose results are emitted as they
+    /// come.
+    current: usize,
+    /// The results of the roots after the current one, held back by root.
+    held: Vec<Vec<T>>,
+    /// The results ready to be emitted, in order.
+    ready: VecDeque<T>,
+    /// Set while a worker is emitting the ready results, see
+    /// `Queue::emit_ready`.
+    emitting: bool,
+}
+
+impl<T> State<T> {
+    /// Readies `result` of the root `seq` to be emitted, or holds it back if
+    /// a root before it is not done yet.
+    fn push(&mut self, seq: usize, result: T) {
+        if self.quit {
+            return;
+        }
+
+        match seq > self.current {
+            true => self.held[seq].push(result),
+            false => self.ready.push_back(result),
+        }
+    }
+
+    /// Marks an item of the root `seq` as processed, readying what was held
+    /// back for the roots after it once it is done.
+    fn finish(&mut self, seq: usize) {
+        self.pending[seq] -= 1;
+
+        while self.current < self.pending.len() && self.pending[self.current] == 0 {
+            self.current += 1;
+
+            let held = match self.held.get_mut(self.current) {
+                Some(held) => std::mem::take(held),
+                None => Vec::new(),
+            };
+            for result in held {
+                self.push(self.current, result);
+            }
+        }
+    }
+}
+
+/// The work queue shared by all worker threads.
+struct Queue<T, E> {
+    state: Mutex<State<T>>,
+    cvar: Condvar,
+    /// Only used by the worker emitting the ready results, outside of the
+    /// lock on the state, so that emitting doesn't hold up the others.
+    emit: Mutex<E>,
+    config: Config,
+}
+
+impl<T, E: FnMut(T) -> WalkState> Queue<T, E> {
+    fn new(roots: Vec<PathBuf>, config: &Config, emit: E) -> Queue<T, E> {
+        let ignore = Ignore::global(config);
+        let items = roots
+            .into_iter()
+            .enumerate()
+            .map(|(seq, path)| Work {
+                path,
+                root: true,
+                seq,
+                ignore: Arc::clone(&ignore),
+            })
+            .collect::<VecDeque<_>>();
+
+        Queue {
+            state: Mutex::new(State {
+                pending: vec![1; items.len()],
+                held: items.iter().map(|_| Vec::new()).collect(),
+                items,
+                active: 0,
+                quit: false,
+                current: 0,
+                ready: VecDeque::new(),
+                emitting: false,
+            }),
+            cvar: Condvar::new(),
+            emit: Mutex::new(emit),
+            config: config.clone(),
+        }
+    }
+
+    /// The worker loop. Returns once the whole tree has been walked.
+    fn run<V>(&self, visit: &V)
+    where
+        V: Fn(&Path, bool) -> (WalkState, Option<T>) + Sync,
+    {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(work) = state.items.pop_front() {
+                state.active += 1;
+                drop(state);
+
+                let seq = work.seq;
+                let mut found = Vec::new();
+                let (walk_state, result) = self.process(work, visit, &mut found);
+
+                state = self.state.lock().unwrap();
+                state.active -= 1;
+                if walk_state == WalkState::Quit {
+                    state.quit = true;
+                }
+                if let Some(result) = result {
+                    state.push(seq, result);
+                }
+                match state.quit {
+                    true => state.items.clear(),
+                    false => {
+                        state.pending[seq] += found.len();
+                        // the entries of a directory go first, so that a
+                        // root is walked to the end before the next one is
+                        // started, which keeps the order of the roots when
+                        // there is a single worker
+                        for work in found.into_iter().rev() {
+                            state.items.push_front(work);
+                        }
+                    }
+                }
+                state.finish(seq);
+                // wake up idle workers, either to pick up new items or to
+                // notice that the walk is finished
+                self.cvar.notify_all();
+
+                state = self.emit_ready(state);
+                continue;
+            }
+
+            if state.active == 0 {
+                return;
+            }
+
+            state = self.cvar.wait(state).unwrap();
+        }
+    }
+
+    /// Emits the ready results, unless another worker already is.
+    ///
+    /// The results are emitted with the state unlocked, so that the other
+    /// workers can carry on in the meantime. Results readied while emitting
+    /// are emitted as well before returning, so that none are left behind.
+    fn emit_ready<'a>(&'a self, mut state: MutexGuard<'a, State<T>>) -> MutexGuard<'a, State<T>> {
+        if state.emitting {
+            return state;
+        }
+
+        state.emitting = true;
+        while !state.ready.is_empty() {
+            let ready = std::mem::take(&mut state.ready);
+            drop(state);
+
+            let mut emit = self.emit.lock().unwrap();
+            let quit = ready
+                .into_iter()
+                .any(|result| emit(result) == WalkState::Quit);
+            drop(emit);
+
+            state = self.state.lock().unwrap();
+            if quit {
+                state.quit = true;
+                state.items.clear();
+                state.ready.clear();
+                self.cvar.notify_all();
+            }
+        }
+        state.emitting = false;
+
+        state
+    }
+
+    /// Processes a single path, adding the entries found to `found` if it is
+    /// a directory.
+    fn process<V>(&self, work: Work, visit: &V, found: &mut Vec<Work>) -> (WalkState, Option<T>)
+    where
+        V: Fn(&Path, bool) -> (WalkState, Option<T>) + Sync,
+    {
+        // standard input is searched as is, there is nothing to walk
+        if work.root && work.path == Path::new(args::STDIN) {
+            return visit(&work.path, work.root);
+        }
+
+        // roots are allowed to be symbolic links, everything else is not
+        let metadata = match work.root {
+            true => std::fs::metadata(&work.path),
+            false => std::fs::symlink_metadata(&work.path),
+        };
+
+        let file_type = match metadata {
+            Ok(metadata) => metadata.file_type(),
+            Err(err) => {
+                err_message!("{}: {}", work.path.display(), err);
+                return (WalkState::Continue, None);
+            }
+        };
+
+        if self.is_skipped(&work, file_type.is_dir()) {
+            return (WalkState::Continue, None);
+        }
+
+        if file_type.is_file() {
+            return visit(&work.path, work.root);
+        }
+
+        if !file_type.is_dir() {
+            return (WalkState::Continue, None);
+        }
+
+        // the rules of the directories above a root apply to it as well
+        let ignore = match work.root {
+            true => work.ignore.above(&self.config, &work.path),
+            false => Arc::clone(&work.ignore),
+        };
+        let ignore = ignore.child(&self.config, &work.path);
+
+        let entries = match std::fs::read_dir(&work.path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                err_message!("{}: {}", work.path.display(), err);
+                return (WalkState::Continue, None);
+            }
+        };
+
+        for entry in entries {
+            match entry {
+                Ok(entry) => found.push(Work {
+                    path: entry.path(),
+                    root: false,
+                    seq: work.seq,
+                    ignore: Arc::clone(&ignore),
+                }),
+                Err(err) => {
+                    err_message!("{}: {}", work.path.display(), err);
+                }
+            }
+        }
+
+        // keep the walk order stable between runs
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        (WalkState::Continue, None)
+    }
+
+    /// Returns true if a path is excluded by the globs, or for an entry found
+    /// while walking, if it is hidden, ignored or not of the file types asked
+    /// for.
+    fn is_skipped(&self, work: &Work, is_dir: bool) -> bool {
+        // a directory given by the user is walked whatever its name
+        if work.root && is_dir {
+            return false;
+        }
+
+        match self.config.globs.matched(&work.path, is_dir) {
+            Match::None => {}
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+        }
+
+        if work.root {
+            return false;
+        }
+
+        let hidden = work
+            .path
+            .file_name()
+            .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."));
+        if hidden && !self.config.hidden {
+            return true;
+        }
+
+        if work.ignore.is_ignored(&work.path, is_dir) {
+            return true;
+        }
+
+        // file types only narrow down what is left
+        matches!(
+            self.config.types.matched(&work.path, is_dir),
+            Match::Ignore(_)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory that is removed with everything in it when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        /// Creates an empty directory, along with `files`, each given by its
+        /// path relative to the directory and its contents.
+        fn new(name: &str, files: &[(&str, &str)]) -> TempDir {
+            let dir =
+                std::env::temp_dir().join(format!("minigrep-walk-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+
+            for (path, contents) in files {
+                let path = dir.join(path);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, contents).unwrap();
+            }
+            std::fs::create_dir_all(&dir).unwrap();
+
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn config(threads: usize) -> Config {
+        Config {
+            threads,
+            hidden: false,
+            ignore: true,
+            globs: Override::empty(),
+            types: ignore::types::Types::empty(),
+        }
+    }
+
+    /// Returns the files found by walking `roots`, relative to `dir`, in
+    /// the order they were visited and in the order they were emitted.
+    fn walked(dir: &Path, roots: &[&str], config: &Config) -> (Vec<String>, Vec<String>) {
+        let roots = roots.iter().map(|root| dir.join(root)).collect();
+        let relative = |path: &Path| {
+            let path = path.strip_prefix(dir).unwrap();
+            path.to_string_lossy().replace('\\', "/")
+        };
+
+        let visited = Mutex::new(Vec::new());
+        let mut emitted = Vec::new();
+        walk(
+            roots,
+            config,
+            |path, _| {
+                visited.lock().unwrap().push(relative(path));
+                (WalkState::Continue, Some(relative(path)))
+            },
+            |path| {
+                emitted.push(path);
+                WalkState::Continue
+            },
+        );
+
+        (visited.into_inner().unwrap(), emitted)
+    }
+
+    #[test]
+    fn roots_are_walked_in_order() {
+        let dir = TempDir::new(
+            "order",
+            &[
+                ("a/x", ""),
+                ("a/sub/y", ""),
+                ("b", ""),
+                ("c/z", ""),
+                ("d", ""),
+            ],
+        );
+        let roots = ["d", "a", "b", "c"];
+        let expected = ["d", "a/sub/y", "a/x", "b", "c/z"];
+
+        // a single worker walks each root to the end before the next one,
+        // which is what output written as files are visited relies on
+        let (visited, emitted) = walked(&dir.0, &roots, &config(1));
+        assert_eq!(visited, expected);
+        assert_eq!(emitted, expected);
+
+        for _ in 0..10 {
+            let (_, mut emitted) = walked(&dir.0, &roots, &config(4));
+            // the files of a directory come in no particular order
+            emitted[1..3].sort();
+            assert_eq!(emitted, expected);
+        }
+    }
+
+    /// Returns the files found by walking `roots`, relative to `dir`, in
+    /// sorted order.
+    fn found(dir: &Path, roots: &[&str]) -> Vec<String> {
+        let (_, mut emitted) = walked(dir, roots, &config(1));
+        emitted.sort();
+        emitted
+    }
+
+    #[test]
+    fn negated_rules_win_over_earlier_ones() {
+        let dir = TempDir::new(
+            "negate",
+            &[
+                (".gitignore", "*.log\n!keep.log\n"),
+                ("a.log", ""),
+                ("keep.log", ""),
+                ("b.txt", ""),
+                ("sub/.gitignore", "!c.log\n"),
+                ("sub/c.log", ""),
+                ("sub/d.log", ""),
+            ],
+        );
+
+        assert_eq!(found(&dir.0, &["."]), ["b.txt", "keep.log", "sub/c.log"]);
+    }
+
+    #[test]
+    fn rules_with_a_slash_are_anchored() {
+        let dir = TempDir::new(
+            "anchored",
+            &[
+                (".gitignore", "/anchored\nnested/anchored\n"),
+                ("anchored", ""),
+                ("sub/anchored", ""),
+                ("nested/anchored", ""),
+                ("sub/nested/anchored", ""),
+            ],
+        );
+
+        assert_eq!(
+            found(&dir.0, &["."]),
+            ["sub/anchored", "sub/nested/anchored"]
+        );
+    }
+
+    #[test]
+    fn directory_rules_only_apply_to_directories() {
+        let dir = TempDir::new(
+            "dir-rule",
+            &[
+                (".gitignore", "build/\n"),
+                ("build", ""),
+                ("sub/build/x", ""),
+            ],
+        );
+
+        assert_eq!(found(&dir.0, &["."]), ["build"]);
+    }
+
+    #[test]
+    fn ignore_files_win_over_gitignore_files() {
+        let dir = TempDir::new(
+            "precedence",
+            &[
+                (".gitignore", "*.txt\n!a.log\n"),
+                (".ignore", "!keep.txt\n*.log\n"),
+                ("keep.txt", ""),
+                ("other.txt", ""),
+                ("a.log", ""),
+            ],
+        );
+
+        assert_eq!(found(&dir.0, &["."]), ["keep.txt"]);
+    }
+
+    #[test]
+    fn rules_above_a_root_apply_up_to_the_repository() {
+        let dir = TempDir::new(
+            "above",
+            &[
+                (".gitignore", "*\n"),
+                ("repo/.git/HEAD", ""),
+                ("repo/.gitignore", "/sub/skip\n*.log\n"),
+                ("repo/sub/.gitignore", "!b.log\n"),
+                ("repo/sub/skip", ""),
+                ("repo/sub/keep", ""),
+                ("repo/sub/a.log", ""),
+                ("repo/sub/b.log", ""),
+                ("repo/sub/deeper/skip", ""),
+            ],
+        );
+
+        // the rules outside of the repository don't apply
+        assert_eq!(
+            found(&dir.0, &["repo/sub"]),
+            ["repo/sub/b.log", "repo/sub/deeper/skip", "repo/sub/keep"]
+        );
+    }
+}